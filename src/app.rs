@@ -7,16 +7,23 @@
 
 use crate::config::{Config, LayoutMode};
 use crate::nix::{
-    self, CommandResult, GenerationSource, SystemInfo,
-    delete_generations, get_packages, list_generations, restore_generation,
+    self, CommandResult, GenerationSource, HistoryEntry, NixBackend, PackageCache, SystemBackend,
+    SystemInfo, WhyNode,
 };
-use crate::types::{Generation, GenerationDiff, Package, ProfileType, Tab};
+use crate::policy;
+use crate::report::{self, Report, ReportDiff, ReportFormat};
+use crate::tasks::{self, Progress, Task, TaskEvent};
+use crate::types::{DiffSide, DiffSort, Generation, GenerationDiff, Package, ProfileId, Tab, UpdateKind};
 use crate::ui::Theme;
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent};
-use std::collections::HashSet;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+/// Message of the Loading popup while all generations are (re)loaded
+const LOADING_GENERATIONS: &str = "Loading generations";
+
 /// Main application state
 pub struct App {
     // Core state
@@ -26,40 +33,47 @@ pub struct App {
     pub theme: Theme,
     pub system_info: SystemInfo,
     pub dry_run: bool,
+    pub backend: Arc<dyn NixBackend>,
 
     // System generations
     pub system_generations: Vec<Generation>,
     pub system_source: GenerationSource,
 
-    // Home-Manager generations (optional)
-    pub home_manager_generations: Option<Vec<Generation>>,
-    pub home_manager_source: Option<GenerationSource>,
+    // Home-Manager, `nix profile` and config.toml profiles (if present)
+    pub other_profiles: Vec<ProfileGenerations>,
 
     // Overview tab state
-    pub overview_focus: usize,           // 0 = System, 1 = HM
-    pub overview_system_selected: usize,
-    pub overview_hm_selected: usize,
+    pub overview_focus: usize,           // Index into profiles()
+    pub overview_selected: HashMap<ProfileId, usize>,
 
     // Packages tab state
     pub packages_list: Vec<Package>,
     pub packages_gen_id: Option<u32>,
-    pub packages_profile: ProfileType,
+    pub packages_specialisation: Option<String>,
+    pub packages_profile: ProfileId,
     pub packages_selected: usize,
     pub packages_filter: String,
+    pub package_cache: Arc<Mutex<PackageCache>>, // Package lists per generation (for history)
 
     // Diff tab state - FIX: Add cursors for selection lists
+    pub diff_profile: ProfileId,
     pub diff_focus: usize,               // 0 = From list, 1 = To list
     pub diff_from_cursor: usize,         // NEW: Cursor in From list
     pub diff_to_cursor: usize,           // NEW: Cursor in To list
-    pub diff_from_gen: Option<u32>,
-    pub diff_to_gen: Option<u32>,
+    pub diff_from: Option<DiffSide>,
+    pub diff_to: Option<DiffSide>,
     pub diff_scroll: usize,
+    pub diff_sort: DiffSort,
+    pub diff_kind_filter: Option<UpdateKind>, // Only show updates of this kind
+    pub diff_full_closure: bool,              // Compare every store path, not just packages
     pub current_diff: Option<GenerationDiff>,
 
     // Manage tab state
-    pub manage_profile: ProfileType,
+    pub manage_profile: ProfileId,
     pub manage_cursor: usize,
     pub manage_selected: HashSet<u32>,
+    pub policy_candidates: HashSet<u32>, // Would be deleted by the retention policy
+    pub restore_specialisation: Option<String>, // Target of the restore prompt
 
     // Settings tab state
     pub settings_selected: usize,
@@ -67,6 +81,10 @@ pub struct App {
     // Popup state
     pub popup: PopupState,
 
+    // Background task (shown as the Loading popup while it runs)
+    pub task: Option<Task>,
+    pub unreadable_profiles: Vec<String>, // Configured profiles that failed to load
+
     // Flash message (temporary feedback)
     pub flash_message: Option<(String, bool, Instant)>, // (message, is_error, timestamp)
 
@@ -74,6 +92,13 @@ pub struct App {
     pub pending_undo: Option<PendingUndo>,
 }
 
+/// Generations of a profile other than System
+#[derive(Debug, Clone)]
+pub struct ProfileGenerations {
+    pub source: GenerationSource,
+    pub generations: Vec<Generation>,
+}
+
 /// Popup overlay state
 #[derive(Debug, Clone)]
 pub enum PopupState {
@@ -93,119 +118,141 @@ pub enum PopupState {
     },
     Loading {
         message: String,
+        progress: Option<Progress>,
+    },
+    Why {
+        title: String,
+        tree: WhyNode,
+        cursor: usize,
+        collapsed: HashSet<usize>, // Pre-order indices of collapsed nodes
+    },
+    History {
+        package: String,
+        entries: Vec<HistoryEntry>,
+        scroll: usize,
+    },
+    PathInput {
+        input: String, // Store path or link for the focused Diff side
     },
 }
 
 /// Pending undo action
 #[derive(Debug, Clone)]
 pub struct PendingUndo {
-    pub action: UndoAction,
     pub started_at: Instant,
 }
 
-/// Action that can be undone
-#[derive(Debug, Clone)]
-pub enum UndoAction {
-    Delete {
-        profile: ProfileType,
-        generation_ids: Vec<u32>,
-    },
-}
-
 /// Application state machine
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     Normal,
     FilterInput,
-    DropdownOpen,
     ConfirmAction,
     ShowError,
     UndoCountdown,
     Loading,
+    WhyTree,
+    PackageHistory,
+    PathInput,
 }
 
 impl App {
     /// Create a new App instance
-    pub fn new(system_info: SystemInfo, config: Config, dry_run: bool) -> Result<Self> {
+    pub fn new(system_info: SystemInfo, config: Config, dry_run: bool) -> Self {
+        Self::with_backend(system_info, config, dry_run, Arc::new(SystemBackend))
+    }
+
+    /// Create an App running Nix operations through the given backend
+    ///
+    /// Generations are loaded in the background; see `poll_tasks`.
+    pub fn with_backend(
+        system_info: SystemInfo,
+        config: Config,
+        dry_run: bool,
+        backend: Arc<dyn NixBackend>,
+    ) -> Self {
         let theme = Theme::from_name(config.theme);
 
         // System generations source
-        let system_source = GenerationSource {
-            profile_type: ProfileType::System,
-            profile_path: system_info.system_profile.clone(),
-        };
-
-        // Load system generations
-        let mut system_generations = list_generations(&system_source)?;
-        
-        // Apply pinned status from config
-        for gen in &mut system_generations {
-            gen.is_pinned = config.is_system_pinned(gen.id);
-        }
-
-        // Home-Manager source (if detected)
-        let (home_manager_source, home_manager_generations) = 
-            if let Some(hm_info) = &system_info.home_manager {
-                let source = GenerationSource {
-                    profile_type: ProfileType::HomeManager,
-                    profile_path: hm_info.profile_path.clone(),
-                };
-                
-                match list_generations(&source) {
-                    Ok(mut gens) => {
-                        for gen in &mut gens {
-                            gen.is_pinned = config.is_home_manager_pinned(gen.id);
-                        }
-                        (Some(source), Some(gens))
-                    }
-                    Err(_) => (None, None), // Graceful degradation
-                }
-            } else {
-                (None, None)
-            };
-
-        Ok(Self {
+        let system_source = GenerationSource::system(&system_info);
+
+        // Home-Manager and `nix profile` sources (if detected), then the
+        // profiles declared in config.toml. Profiles that fail to load are
+        // dropped once their generations arrive.
+        let other_profiles: Vec<ProfileGenerations> = [
+            GenerationSource::home_manager(&system_info),
+            GenerationSource::nix_profile(&system_info),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(config.profiles.iter().map(GenerationSource::custom))
+        .map(|source| ProfileGenerations { source, generations: Vec::new() })
+        .collect();
+
+        let mut app = Self {
             should_quit: false,
             active_tab: Tab::Overview,
             config,
             theme,
             system_info,
             dry_run,
+            backend,
 
-            system_generations,
+            system_generations: Vec::new(),
             system_source,
 
-            home_manager_generations,
-            home_manager_source,
+            other_profiles,
 
             overview_focus: 0,
-            overview_system_selected: 0,
-            overview_hm_selected: 0,
+            overview_selected: HashMap::new(),
 
             packages_list: Vec::new(),
             packages_gen_id: None,
-            packages_profile: ProfileType::System,
+            packages_specialisation: None,
+            packages_profile: ProfileId::SYSTEM,
             packages_selected: 0,
             packages_filter: String::new(),
+            package_cache: Arc::new(Mutex::new(PackageCache::new())),
 
+            diff_profile: ProfileId::SYSTEM,
             diff_focus: 0,
             diff_from_cursor: 0,      // NEW: Initialize cursors
             diff_to_cursor: 0,        // NEW: Initialize cursors
-            diff_from_gen: None,
-            diff_to_gen: None,
+            diff_from: None,
+            diff_to: None,
             diff_scroll: 0,
+            diff_sort: DiffSort::Name,
+            diff_kind_filter: None,
+            diff_full_closure: false,
             current_diff: None,
 
-            manage_profile: ProfileType::System,
+            manage_profile: ProfileId::SYSTEM,
             manage_cursor: 0,
             manage_selected: HashSet::new(),
+            restore_specialisation: None,
+            policy_candidates: HashSet::new(),
 
             settings_selected: 0,
 
             popup: PopupState::None,
+            task: None,
+            unreadable_profiles: Vec::new(),
             flash_message: None,
             pending_undo: None,
-        })
+        };
+
+        app.load_generations();
+        app
+    }
+
+    /// Load the generations of all profiles in the background (also Ctrl+R)
+    fn load_generations(&mut self) {
+        let sources: Vec<GenerationSource> = std::iter::once(self.system_source.clone())
+            .chain(self.other_profiles.iter().map(|p| p.source.clone()))
+            .collect();
+        let closure_counts = self.config.display.show_closure_count;
+        let task = tasks::load_generations(Arc::clone(&self.backend), sources, closure_counts);
+        self.start_task(task, LOADING_GENERATIONS);
     }
 
     /// Get current app state
@@ -222,9 +269,47 @@ impl App {
             PopupState::Error { .. } => AppState::ShowError,
             PopupState::Undo { .. } => AppState::UndoCountdown,
             PopupState::Loading { .. } => AppState::Loading,
+            PopupState::Why { .. } => AppState::WhyTree,
+            PopupState::History { .. } => AppState::PackageHistory,
+            PopupState::PathInput { .. } => AppState::PathInput,
         }
     }
 
+    /// Focus a profile in the Overview and Manage tabs (e.g. from `--profile`)
+    pub fn select_profile(&mut self, profile: &ProfileId) {
+        let Some(index) = self.profiles().iter().position(|p| p == profile) else {
+            return;
+        };
+        self.overview_focus = index;
+        self.manage_profile = profile.clone();
+        self.update_policy_candidates();
+    }
+
+    /// Profiles with generations, in display order
+    pub fn profiles(&self) -> Vec<ProfileId> {
+        std::iter::once(ProfileId::SYSTEM)
+            .chain(self.other_profiles.iter().map(|p| p.source.id.clone()))
+            .collect()
+    }
+
+    /// Generations of a profile (System's if the profile was not found)
+    pub fn generations_for(&self, profile: &ProfileId) -> &[Generation] {
+        self.other_profiles
+            .iter()
+            .find(|p| &p.source.id == profile)
+            .map_or(&self.system_generations, |p| &p.generations)
+    }
+
+    /// Profile focused in the Overview tab
+    pub fn overview_profile(&self) -> ProfileId {
+        self.profiles().get(self.overview_focus).cloned().unwrap_or(ProfileId::SYSTEM)
+    }
+
+    /// Cursor of a profile's list in the Overview tab
+    pub fn overview_cursor(&self, profile: &ProfileId) -> usize {
+        self.overview_selected.get(profile).copied().unwrap_or(0)
+    }
+
     /// Check if side-by-side layout should be used
     pub fn should_use_side_by_side(&self, terminal_width: u16) -> bool {
         match self.config.layout {
@@ -248,8 +333,11 @@ impl App {
             AppState::ConfirmAction => self.handle_confirm_key(key),
             AppState::ShowError => self.handle_error_key(key),
             AppState::UndoCountdown => self.handle_undo_key(key),
-            AppState::Loading => Ok(()), // Ignore input while loading
-            AppState::Normal | AppState::FilterInput | AppState::DropdownOpen => {
+            AppState::Loading => self.handle_loading_key(key),
+            AppState::WhyTree => self.handle_why_key(key),
+            AppState::PackageHistory => self.handle_history_key(key),
+            AppState::PathInput => self.handle_path_input_key(key),
+            AppState::Normal | AppState::FilterInput => {
                 self.handle_normal_key(key)
             }
         }
@@ -258,6 +346,10 @@ impl App {
     /// Handle key in normal state
     fn handle_normal_key(&mut self, key: KeyEvent) -> Result<()> {
         // Global keys (work in all tabs)
+        if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.load_generations();
+            return Ok(());
+        }
         match key.code {
             KeyCode::Char('q') => {
                 self.should_quit = true;
@@ -283,57 +375,32 @@ impl App {
 
     /// Handle keys in Overview tab
     fn handle_overview_key(&mut self, key: KeyEvent) -> Result<()> {
-        let has_hm = self.home_manager_generations.is_some();
+        let profile_count = self.profiles().len();
+        let profile = self.overview_profile();
+        let last = self.generations_for(&profile).len().saturating_sub(1);
+        let cursor = self.overview_selected.entry(profile.clone()).or_insert(0);
 
         match key.code {
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.overview_focus == 0 {
-                    if self.overview_system_selected < self.system_generations.len().saturating_sub(1) {
-                        self.overview_system_selected += 1;
-                    }
-                } else if let Some(hm) = &self.home_manager_generations {
-                    if self.overview_hm_selected < hm.len().saturating_sub(1) {
-                        self.overview_hm_selected += 1;
-                    }
-                }
+            KeyCode::Char('j') | KeyCode::Down if *cursor < last => {
+                *cursor += 1;
             }
             KeyCode::Char('k') | KeyCode::Up => {
-                if self.overview_focus == 0 {
-                    self.overview_system_selected = self.overview_system_selected.saturating_sub(1);
-                } else {
-                    self.overview_hm_selected = self.overview_hm_selected.saturating_sub(1);
-                }
+                *cursor = cursor.saturating_sub(1);
             }
             KeyCode::Char('g') => {
-                if self.overview_focus == 0 {
-                    self.overview_system_selected = 0;
-                } else {
-                    self.overview_hm_selected = 0;
-                }
+                *cursor = 0;
             }
             KeyCode::Char('G') => {
-                if self.overview_focus == 0 {
-                    self.overview_system_selected = self.system_generations.len().saturating_sub(1);
-                } else if let Some(hm) = &self.home_manager_generations {
-                    self.overview_hm_selected = hm.len().saturating_sub(1);
-                }
+                *cursor = last;
             }
-            KeyCode::Tab => {
-                if has_hm {
-                    self.overview_focus = (self.overview_focus + 1) % 2;
-                }
+            KeyCode::Tab if profile_count > 1 => {
+                self.overview_focus = (self.overview_focus + 1) % profile_count;
             }
             KeyCode::Enter => {
                 // Switch to Packages tab with selected generation
-                let (gen, profile) = if self.overview_focus == 0 {
-                    (self.system_generations.get(self.overview_system_selected), ProfileType::System)
-                } else {
-                    let hm = self.home_manager_generations.as_ref();
-                    (hm.and_then(|g| g.get(self.overview_hm_selected)), ProfileType::HomeManager)
-                };
-
-                if let Some(gen) = gen {
-                    self.load_packages(gen.id, profile)?;
+                let cursor = *cursor;
+                if let Some(gen) = self.generations_for(&profile).get(cursor) {
+                    self.load_packages(gen.id, profile, None)?;
                     self.active_tab = Tab::Packages;
                 }
             }
@@ -378,6 +445,38 @@ impl App {
             KeyCode::Char('G') => {
                 self.packages_selected = self.filtered_packages_count().saturating_sub(1);
             }
+            KeyCode::Enter => {
+                // Version history of the selected package
+                if let Some(pkg) = self.selected_package() {
+                    let name = pkg.name.clone();
+                    self.show_history(&name, &self.packages_profile.clone());
+                }
+            }
+            KeyCode::Char('w') => {
+                // Why is the selected package in this generation?
+                if let (Some(pkg), Some(gen_id)) = (self.selected_package(), self.packages_gen_id) {
+                    let name = pkg.name.clone();
+                    let source = self.source_for(&self.packages_profile).clone();
+                    self.show_why(
+                        &format!("Why {} is in #{}", name, gen_id),
+                        &source.configuration_path(gen_id, self.packages_specialisation.as_deref()),
+                        &[name.as_str()],
+                    );
+                }
+            }
+            KeyCode::Char('s') => {
+                // Cycle through the generation's specialisations
+                if let Some(gen_id) = self.packages_gen_id {
+                    let profile = self.packages_profile.clone();
+                    match self.specialisations_of(&profile, gen_id) {
+                        [] => self.show_flash("Generation has no specialisations", true),
+                        names => {
+                            let next = next_specialisation(self.packages_specialisation.as_deref(), names);
+                            self.load_packages(gen_id, profile, next)?;
+                        }
+                    }
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -385,19 +484,30 @@ impl App {
 
     /// Handle keys in Diff tab - COMPLETELY REWRITTEN
     fn handle_diff_key(&mut self, key: KeyEvent) -> Result<()> {
+        let last = self.generations_for(&self.diff_profile).len().saturating_sub(1);
+
         match key.code {
             KeyCode::Tab => {
                 // Switch between From and To lists
                 self.diff_focus = (self.diff_focus + 1) % 2;
             }
+            KeyCode::Char('p') if self.profiles().len() > 1 => {
+                // Compare the generations of the next profile
+                let profiles = self.profiles();
+                let index = profiles.iter().position(|p| *p == self.diff_profile).unwrap_or(0);
+                self.diff_profile = profiles[(index + 1) % profiles.len()].clone();
+                self.clear_diff();
+                self.diff_from_cursor = 0;
+                self.diff_to_cursor = 0;
+            }
             KeyCode::Char('j') | KeyCode::Down => {
                 // Navigate in active list
                 if self.diff_focus == 0 {
-                    if self.diff_from_cursor < self.system_generations.len().saturating_sub(1) {
+                    if self.diff_from_cursor < last {
                         self.diff_from_cursor += 1;
                     }
                 } else {
-                    if self.diff_to_cursor < self.system_generations.len().saturating_sub(1) {
+                    if self.diff_to_cursor < last {
                         self.diff_to_cursor += 1;
                     }
                 }
@@ -420,40 +530,88 @@ impl App {
             }
             KeyCode::Char('G') => {
                 // Jump to bottom of active list
-                let max = self.system_generations.len().saturating_sub(1);
                 if self.diff_focus == 0 {
-                    self.diff_from_cursor = max;
+                    self.diff_from_cursor = last;
                 } else {
-                    self.diff_to_cursor = max;
+                    self.diff_to_cursor = last;
                 }
             }
             KeyCode::Enter => {
                 // Select generation from active list
-                let gen_id = if self.diff_focus == 0 {
-                    self.system_generations.get(self.diff_from_cursor).map(|g| g.id)
-                } else {
-                    self.system_generations.get(self.diff_to_cursor).map(|g| g.id)
-                };
+                let cursor = if self.diff_focus == 0 { self.diff_from_cursor } else { self.diff_to_cursor };
+                let gen_id = self.generations_for(&self.diff_profile).get(cursor).map(|g| g.id);
 
                 if let Some(id) = gen_id {
-                    if self.diff_focus == 0 {
-                        self.diff_from_gen = Some(id);
+                    self.select_diff_side(DiffSide::generation(id))?;
+                }
+            }
+            KeyCode::Char('o') => {
+                // Compare a store path or build result instead of a generation,
+                // as the To side (against the current generation) unless a
+                // From side is already selected
+                if self.diff_from.is_none() {
+                    self.diff_focus = 1;
+                }
+                self.popup = PopupState::PathInput { input: "./result".into() };
+            }
+            KeyCode::Char('e') => self.export_report(ReportFormat::Markdown),
+            KeyCode::Char('E') => self.export_report(ReportFormat::Html),
+            KeyCode::Char('w') => {
+                // Why were the added packages pulled in?
+                if let (Some(diff), Some(to)) = (&self.current_diff, &self.diff_to) {
+                    if diff.added.is_empty() {
+                        self.show_flash("No added packages", false);
                     } else {
-                        self.diff_to_gen = Some(id);
+                        let names: Vec<String> = diff.added.iter().map(|p| p.name.clone()).collect();
+                        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                        let gen_path = self.source_for(&self.diff_profile).side_path(to);
+                        self.show_why(&format!("Why packages were added in {}", to), &gen_path, &names);
                     }
-
-                    // Automatically calculate diff if both are selected
-                    if self.diff_from_gen.is_some() && self.diff_to_gen.is_some() {
+                }
+            }
+            KeyCode::Char('s') => {
+                // Compare a specialisation of the selected generation instead of its base
+                let side = if self.diff_focus == 0 { &self.diff_from } else { &self.diff_to };
+                let Some((gen_id, current)) = side.as_ref().and_then(|s| Some((s.gen_id()?, s.specialisation()))) else {
+                    self.show_flash("Select a generation first", true);
+                    return Ok(());
+                };
+                match self.specialisations_of(&self.diff_profile, gen_id) {
+                    [] => self.show_flash("Generation has no specialisations", true),
+                    names => {
+                        let specialisation = next_specialisation(current, names);
+                        let side = Some(DiffSide::Generation { id: gen_id, specialisation });
+                        if self.diff_focus == 0 {
+                            self.diff_from = side;
+                        } else {
+                            self.diff_to = side;
+                        }
                         self.calculate_diff()?;
                     }
                 }
             }
+            KeyCode::Char('z') => {
+                // Toggle between alphabetical order and largest size change first
+                self.diff_sort = match self.diff_sort {
+                    DiffSort::Name => DiffSort::Size,
+                    DiffSort::Size => DiffSort::Name,
+                };
+                if let Some(diff) = &mut self.current_diff {
+                    diff.sort(self.diff_sort);
+                }
+            }
+            KeyCode::Char('f') => {
+                // Cycle through the update kinds shown
+                self.diff_kind_filter = next_update_kind(self.diff_kind_filter);
+            }
+            KeyCode::Char('m') => {
+                // Toggle between the package and the full-closure diff
+                self.diff_full_closure = !self.diff_full_closure;
+                self.calculate_diff()?;
+            }
             KeyCode::Char('c') | KeyCode::Char('C') => {
                 // Clear selection
-                self.diff_from_gen = None;
-                self.diff_to_gen = None;
-                self.current_diff = None;
-                self.diff_scroll = 0;
+                self.clear_diff();
             }
             _ => {}
         }
@@ -462,50 +620,49 @@ impl App {
 
     /// Handle keys in Manage tab
     fn handle_manage_key(&mut self, key: KeyEvent) -> Result<()> {
-        let generations = if self.manage_profile == ProfileType::System {
-            &self.system_generations
-        } else {
-            self.home_manager_generations.as_ref().unwrap_or(&self.system_generations)
-        };
+        let generations = self.generations_for(&self.manage_profile);
+        let last = generations.len().saturating_sub(1);
+        let under_cursor = generations.get(self.manage_cursor).map(|g| (g.id, g.is_current));
+        let selectable: Vec<u32> = generations
+            .iter()
+            .filter(|g| !g.is_current && !g.is_pinned)
+            .map(|g| g.id)
+            .collect();
 
         match key.code {
-            KeyCode::Tab => {
-                if self.home_manager_generations.is_some() {
-                    self.manage_profile = match self.manage_profile {
-                        ProfileType::System => ProfileType::HomeManager,
-                        ProfileType::HomeManager => ProfileType::System,
-                    };
-                    self.manage_cursor = 0;
-                    self.manage_selected.clear();
-                }
+            KeyCode::Tab if self.profiles().len() > 1 => {
+                let profiles = self.profiles();
+                let index = profiles.iter().position(|p| *p == self.manage_profile).unwrap_or(0);
+                self.manage_profile = profiles[(index + 1) % profiles.len()].clone();
+                self.manage_cursor = 0;
+                self.manage_selected.clear();
+                self.update_policy_candidates();
             }
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.manage_cursor < generations.len().saturating_sub(1) {
-                    self.manage_cursor += 1;
-                }
+            KeyCode::Char('j') | KeyCode::Down if self.manage_cursor < last => {
+                self.manage_cursor += 1;
             }
             KeyCode::Char('k') | KeyCode::Up => {
                 self.manage_cursor = self.manage_cursor.saturating_sub(1);
             }
             KeyCode::Char(' ') => {
                 // Toggle selection
-                if let Some(gen) = generations.get(self.manage_cursor) {
-                    if !gen.is_current { // Can't select current generation
-                        if self.manage_selected.contains(&gen.id) {
-                            self.manage_selected.remove(&gen.id);
+                if let Some((id, is_current)) = under_cursor {
+                    if !is_current { // Can't select current generation
+                        if self.manage_selected.contains(&id) {
+                            self.manage_selected.remove(&id);
                         } else {
-                            self.manage_selected.insert(gen.id);
+                            self.manage_selected.insert(id);
                         }
                     }
                 }
             }
             KeyCode::Char('a') | KeyCode::Char('A') => {
                 // Select all (except current and pinned)
-                for gen in generations {
-                    if !gen.is_current && !gen.is_pinned {
-                        self.manage_selected.insert(gen.id);
-                    }
-                }
+                self.manage_selected.extend(selectable);
+            }
+            KeyCode::Char('x') | KeyCode::Char('X') => {
+                // Select what the retention policy would delete
+                self.manage_selected.clone_from(&self.policy_candidates);
             }
             KeyCode::Char('c') | KeyCode::Char('C') => {
                 // Clear selection
@@ -513,8 +670,8 @@ impl App {
             }
             KeyCode::Char('p') | KeyCode::Char('P') => {
                 // Pin/unpin
-                if let Some(gen) = generations.get(self.manage_cursor) {
-                    self.toggle_pin(gen.id)?;
+                if let Some((id, _)) = under_cursor {
+                    self.toggle_pin(id)?;
                 }
             }
             KeyCode::Char('r') | KeyCode::Char('R') => {
@@ -530,15 +687,28 @@ impl App {
         Ok(())
     }
 
+    /// Handle keys while a background task runs
+    fn handle_loading_key(&mut self, key: KeyEvent) -> Result<()> {
+        if key.code == KeyCode::Esc {
+            if let Some(task) = self.task.take() {
+                task.cancel();
+                // Profiles loaded so far are kept; Ctrl+R loads them all again
+                let reloadable =
+                    matches!(&self.popup, PopupState::Loading { message, .. } if message == LOADING_GENERATIONS);
+                self.popup = PopupState::None;
+                self.show_flash(if reloadable { "Cancelled - Ctrl+R reloads" } else { "Cancelled" }, true);
+            }
+        }
+        Ok(())
+    }
+
     /// Handle keys in Settings tab
     fn handle_settings_key(&mut self, key: KeyEvent) -> Result<()> {
-        let settings_count = 7; // Number of settings items
+        let settings_count = 8; // Number of settings items
 
         match key.code {
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.settings_selected < settings_count - 1 {
-                    self.settings_selected += 1;
-                }
+            KeyCode::Char('j') | KeyCode::Down if self.settings_selected < settings_count - 1 => {
+                self.settings_selected += 1;
             }
             KeyCode::Char('k') | KeyCode::Up => {
                 self.settings_selected = self.settings_selected.saturating_sub(1);
@@ -558,6 +728,18 @@ impl App {
                     4 => self.config.display.show_package_count = !self.config.display.show_package_count,
                     5 => self.config.display.show_size = !self.config.display.show_size,
                     6 => self.config.display.show_boot_entry = !self.config.display.show_boot_entry,
+                    7 => {
+                        self.config.display.show_closure_count = !self.config.display.show_closure_count;
+                        if self.config.display.show_closure_count {
+                            self.load_generations(); // Counts are only read when enabled
+                        } else {
+                            let profiles = std::iter::once(&mut self.system_generations)
+                                .chain(self.other_profiles.iter_mut().map(|p| &mut p.generations));
+                            for gen in profiles.flatten() {
+                                gen.closure_count = None;
+                            }
+                        }
+                    }
                     _ => {}
                 }
                 // Save config
@@ -581,6 +763,18 @@ impl App {
             KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
                 self.popup = PopupState::None;
             }
+            KeyCode::Char('s') if matches!(&self.popup, PopupState::Confirm { title, .. } if title.contains("Restore")) => {
+                // Choose the specialisation to switch into
+                if let Some(gen) = self.generations_for(&self.manage_profile).get(self.manage_cursor) {
+                    let mut next = next_specialisation(self.restore_specialisation.as_deref(), &gen.specialisations);
+                    if next.is_none() && gen.is_current {
+                        // The base configuration of the current generation is already active
+                        next = next_specialisation(None, &gen.specialisations);
+                    }
+                    self.restore_specialisation = next;
+                    self.show_restore_confirm();
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -614,6 +808,85 @@ impl App {
         Ok(())
     }
 
+    /// Handle keys in the why tree popup
+    fn handle_why_key(&mut self, key: KeyEvent) -> Result<()> {
+        let PopupState::Why { tree, cursor, collapsed, .. } = &mut self.popup else {
+            return Ok(());
+        };
+        let rows = tree.visible_rows(collapsed);
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down if *cursor < rows.len().saturating_sub(1) => {
+                *cursor += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                *cursor = cursor.saturating_sub(1);
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                // Expand/collapse the node under the cursor
+                if let Some(row) = rows.get(*cursor) {
+                    if !row.node.children.is_empty() && !collapsed.remove(&row.index) {
+                        collapsed.insert(row.index);
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('w') => {
+                self.popup = PopupState::None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle keys in the package history popup
+    fn handle_history_key(&mut self, key: KeyEvent) -> Result<()> {
+        let PopupState::History { entries, scroll, .. } = &mut self.popup else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down if *scroll < entries.len().saturating_sub(1) => {
+                *scroll += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                *scroll = scroll.saturating_sub(1);
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                self.popup = PopupState::None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle keys in the store path input popup
+    fn handle_path_input_key(&mut self, key: KeyEvent) -> Result<()> {
+        let PopupState::PathInput { input } = &mut self.popup else {
+            return Ok(());
+        };
+
+        match key.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.popup = PopupState::None,
+            KeyCode::Enter => {
+                let path = std::path::PathBuf::from(input.trim());
+                self.popup = PopupState::None;
+                match self.backend.store_path(&path) {
+                    Some(store_path) => self.select_diff_side(DiffSide::Path(store_path.into()))?,
+                    None => self.show_error(
+                        "Invalid Path",
+                        &format!("{} is not a store path or a link to one", path.display()),
+                    ),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     /// Update undo countdown timer
     pub fn update_undo_timer(&mut self) -> Result<()> {
         if let Some(pending) = &self.pending_undo {
@@ -640,31 +913,133 @@ impl App {
 
     // === HELPER METHODS ===
 
-    /// Load packages for a generation
-    fn load_packages(&mut self, gen_id: u32, profile: ProfileType) -> Result<()> {
-        let source = if profile == ProfileType::System {
-            &self.system_source
-        } else {
-            self.home_manager_source.as_ref().unwrap_or(&self.system_source)
-        };
-
-        let gen_path = source.profile_path.parent()
-            .unwrap_or(&source.profile_path)
-            .join(format!(
-                "{}-{}-link",
-                if profile == ProfileType::System { "system" } else { "home-manager" },
-                gen_id
-            ));
+    /// Load packages for a generation (or one of its specialisations)
+    fn load_packages(&mut self, gen_id: u32, profile: ProfileId, specialisation: Option<String>) -> Result<()> {
+        let source = self.source_for(&profile).clone();
 
-        self.packages_list = get_packages(&gen_path).unwrap_or_default();
+        self.packages_list.clear();
         self.packages_gen_id = Some(gen_id);
-        self.packages_profile = profile;
+        self.packages_specialisation = specialisation.clone();
+        self.packages_profile = profile.clone();
         self.packages_selected = 0;
         self.packages_filter.clear();
 
+        let message = match &specialisation {
+            Some(name) => format!("Loading packages of #{} ({})", gen_id, name),
+            None => format!("Loading packages of #{}", gen_id),
+        };
+        let task = tasks::load_packages(Arc::clone(&self.backend), source, profile, gen_id, specialisation);
+        self.start_task(task, &message);
+
+        Ok(())
+    }
+
+    /// Specialisations of a generation (none if it is unknown)
+    fn specialisations_of(&self, profile: &ProfileId, gen_id: u32) -> &[String] {
+        self.generations_for(profile)
+            .iter()
+            .find(|g| g.id == gen_id)
+            .map_or(&[], |g| g.specialisations.as_slice())
+    }
+
+    /// Both sides of the Diff tab, once two are selected
+    pub fn diff_sides(&self) -> Option<(DiffSide, DiffSide)> {
+        Some((self.diff_from.clone()?, self.diff_to.clone()?))
+    }
+
+    /// Set the focused side of the Diff tab and compare once both are set
+    ///
+    /// A path on the To side is compared with the current generation
+    /// unless another From side was selected.
+    fn select_diff_side(&mut self, side: DiffSide) -> Result<()> {
+        if self.diff_focus == 0 {
+            self.diff_from = Some(side);
+        } else {
+            self.diff_to = Some(side);
+        }
+
+        if self.diff_from.is_none() && matches!(self.diff_to, Some(DiffSide::Path(_))) {
+            self.diff_from = self
+                .generations_for(&self.diff_profile)
+                .iter()
+                .find(|g| g.is_current)
+                .map(|g| DiffSide::generation(g.id));
+        }
+
+        if self.diff_sides().is_some() {
+            self.calculate_diff()?;
+        }
         Ok(())
     }
 
+    /// Generation source of a profile (System's if the profile was not found)
+    fn source_for(&self, profile: &ProfileId) -> &GenerationSource {
+        self.other_profiles
+            .iter()
+            .find(|p| &p.source.id == profile)
+            .map_or(&self.system_source, |p| &p.source)
+    }
+
+    /// Package under the cursor in the (filtered) package list
+    fn selected_package(&self) -> Option<&Package> {
+        let filter = self.packages_filter.to_lowercase();
+        self.packages_list
+            .iter()
+            .filter(|p| filter.is_empty() || p.name.to_lowercase().contains(&filter))
+            .nth(self.packages_selected)
+    }
+
+    /// Write a report of the Diff tab profile's generations and the
+    /// current diff to the working directory
+    fn export_report(&mut self, format: ReportFormat) {
+        let (Some(diff), Some((from, to))) = (&self.current_diff, self.diff_sides()) else {
+            self.show_flash("Select two generations first", true);
+            return;
+        };
+        // Paths would put slashes into the file name
+        let label = |side: &DiffSide| side.gen_id().map_or_else(|| "path".to_string(), |id| id.to_string());
+        let file_name = format!(
+            "nixhist-{}-{}-{}.{}",
+            self.system_info.hostname, label(&from), label(&to), format.extension()
+        );
+
+        let report = Report {
+            hostname: &self.system_info.hostname,
+            profile: self.diff_profile.clone(),
+            generations: self.generations_for(&self.diff_profile),
+            pins: report::pins(&self.config, &self.diff_profile),
+            diff: Some(ReportDiff { from, to, diff }),
+            created: chrono::Local::now(),
+        };
+
+        match std::fs::write(&file_name, report.render(format)) {
+            Ok(()) => self.show_flash(&format!("Report written to {}", file_name), false),
+            Err(e) => self.show_error("Export Failed", &format!("{}: {}", file_name, e)),
+        }
+    }
+
+    /// Open the version history popup for a package (once read in the background)
+    fn show_history(&mut self, name: &str, profile: &ProfileId) {
+        let source = self.source_for(profile).clone();
+        let generations = self.generations_for(profile).to_vec();
+
+        let task = tasks::load_history(
+            Arc::clone(&self.backend),
+            source,
+            generations,
+            name.into(),
+            Arc::clone(&self.package_cache),
+        );
+        self.start_task(task, &format!("History of {}", name));
+    }
+
+    /// Open the why tree popup for packages in a generation (once traced in the background)
+    fn show_why(&mut self, title: &str, gen_path: &std::path::Path, packages: &[&str]) {
+        let packages = packages.iter().map(|p| p.to_string()).collect();
+        let task = tasks::load_why(Arc::clone(&self.backend), gen_path.to_path_buf(), packages, title.into());
+        self.start_task(task, title);
+    }
+
     /// Count filtered packages
     fn filtered_packages_count(&self) -> usize {
         if self.packages_filter.is_empty() {
@@ -679,101 +1054,242 @@ impl App {
 
     /// Calculate diff between two generations
     fn calculate_diff(&mut self) -> Result<()> {
-        let (from_id, to_id) = match (self.diff_from_gen, self.diff_to_gen) {
-            (Some(from), Some(to)) => (from, to),
-            _ => return Ok(()),
+        let Some((from, to)) = self.diff_sides() else {
+            return Ok(());
         };
 
-        let source = &self.system_source;
-        let parent = source.profile_path.parent().unwrap_or(&source.profile_path);
+        self.current_diff = None;
+        let message = format!("Comparing {} and {}", from, to);
+        let source = self.source_for(&self.diff_profile).clone();
+        let task = tasks::load_diff(Arc::clone(&self.backend), source, from, to, self.diff_full_closure);
+        self.start_task(task, &message);
 
-        let from_path = parent.join(format!("system-{}-link", from_id));
-        let to_path = parent.join(format!("system-{}-link", to_id));
+        Ok(())
+    }
 
-        let from_packages = get_packages(&from_path).unwrap_or_default();
-        let to_packages = get_packages(&to_path).unwrap_or_default();
+    /// Run a background task behind the Loading popup
+    fn start_task(&mut self, task: Task, message: &str) {
+        if let Some(previous) = self.task.replace(task) {
+            previous.cancel();
+        }
+        self.popup = PopupState::Loading {
+            message: message.into(),
+            progress: None,
+        };
+    }
 
-        self.current_diff = Some(GenerationDiff::calculate(&from_packages, &to_packages));
-        self.diff_scroll = 0;
+    /// Apply what the background task has sent since the last call
+    ///
+    /// Called from the main loop on every tick.
+    pub fn poll_tasks(&mut self) {
+        let Some(task) = &self.task else {
+            return;
+        };
 
-        Ok(())
-    }
+        let events = match task.poll() {
+            Ok(events) => events,
+            Err(message) => {
+                self.task = None;
+                self.show_error("Loading Failed", &message);
+                return;
+            }
+        };
 
-    /// Toggle pin status for a generation
-    fn toggle_pin(&mut self, gen_id: u32) -> Result<()> {
-        match self.manage_profile {
-            ProfileType::System => {
-                self.config.toggle_system_pin(gen_id);
-                if let Some(gen) = self.system_generations.iter_mut().find(|g| g.id == gen_id) {
-                    gen.is_pinned = self.config.is_system_pinned(gen_id);
-                }
-            }
-            ProfileType::HomeManager => {
-                self.config.toggle_home_manager_pin(gen_id);
-                if let Some(gens) = &mut self.home_manager_generations {
-                    if let Some(gen) = gens.iter_mut().find(|g| g.id == gen_id) {
-                        gen.is_pinned = self.config.is_home_manager_pinned(gen_id);
+        for event in events {
+            match event {
+                TaskEvent::Progress(update) => {
+                    if let PopupState::Loading { progress, .. } = &mut self.popup {
+                        *progress = Some(update);
                     }
                 }
+                TaskEvent::Profile { source, generations } => self.apply_generations(source, generations),
+                TaskEvent::Packages { profile, gen_id, specialisation, packages } => {
+                    if self.packages_profile == profile
+                        && self.packages_gen_id == Some(gen_id)
+                        && self.packages_specialisation == specialisation
+                    {
+                        self.packages_list = packages.clone();
+                    }
+                    // Base configurations are what the history compares; skipped
+                    // while a cancelled history task still holds the cache
+                    let gen = self.generations_for(&profile).iter().find(|g| g.id == gen_id);
+                    if let (Some(gen), None, Ok(mut cache)) = (gen, &specialisation, self.package_cache.try_lock()) {
+                        if !packages.is_empty() {
+                            cache.insert(self.source_for(&profile), gen, packages);
+                        }
+                    }
+                }
+                TaskEvent::History { package, entries } => {
+                    self.popup = PopupState::History { package, entries, scroll: 0 };
+                }
+                TaskEvent::Diff { profile, from, to, diff } => {
+                    if self.diff_profile == profile && self.diff_sides() == Some((from, to)) {
+                        match diff {
+                            Ok(mut diff) => {
+                                diff.sort(self.diff_sort);
+                                self.current_diff = Some(diff);
+                                self.diff_scroll = 0;
+                            }
+                            Err(e) => self.show_error("Diff Failed", &format!("{:#}", e)),
+                        }
+                    }
+                }
+                TaskEvent::Why { title, tree } => match tree {
+                    Ok(Some(tree)) => {
+                        self.popup = PopupState::Why { title, tree, cursor: 0, collapsed: HashSet::new() };
+                    }
+                    Ok(None) => self.show_flash("Not found in the generation's closure", true),
+                    Err(e) => self.show_error("Why Failed", &format!("{:#}", e)),
+                },
+                TaskEvent::Done => {
+                    self.task = None;
+                    if matches!(self.popup, PopupState::Loading { .. }) {
+                        self.popup = PopupState::None;
+                    }
+                    if !self.unreadable_profiles.is_empty() {
+                        let names = std::mem::take(&mut self.unreadable_profiles);
+                        self.show_flash(&format!("Cannot read profile(s): {}", names.join(", ")), true);
+                    }
+                    return;
+                }
             }
         }
+    }
+
+    /// Store the generations of a profile loaded in the background
+    fn apply_generations(&mut self, source: GenerationSource, generations: Result<Vec<Generation>>) {
+        let profile = source.id.clone();
+        match generations {
+            Ok(mut generations) => {
+                for gen in &mut generations {
+                    gen.is_pinned = self.config.is_pinned(&profile, gen.id);
+                }
+                *self.generations_for_mut(&profile) = generations;
+            }
+            Err(e) if profile == ProfileId::SYSTEM => {
+                self.show_error("Failed to load generations", &format!("{:#}", e));
+                return;
+            }
+            Err(_) => {
+                // Graceful degradation: hide the profile, but report
+                // configured ones (detected ones may simply be unused)
+                if matches!(profile, ProfileId::Custom(_)) {
+                    self.unreadable_profiles.push(profile.name().to_string());
+                }
+                self.remove_profile(&profile);
+            }
+        }
+        self.update_policy_candidates();
+    }
+
+    /// Drop a profile that could not be loaded, keeping the focus on the others
+    fn remove_profile(&mut self, profile: &ProfileId) {
+        let focused = self.overview_profile();
+        self.other_profiles.retain(|p| &p.source.id != profile);
+        self.overview_focus = self.profiles().iter().position(|p| *p == focused).unwrap_or(0);
+        if &self.manage_profile == profile {
+            self.manage_profile = ProfileId::SYSTEM;
+            self.manage_cursor = 0;
+            self.manage_selected.clear();
+        }
+        if &self.diff_profile == profile {
+            self.diff_profile = ProfileId::SYSTEM;
+            self.clear_diff();
+        }
+    }
+
+    /// Clear the Diff tab selection and result
+    fn clear_diff(&mut self) {
+        self.diff_from = None;
+        self.diff_to = None;
+        self.current_diff = None;
+        self.diff_scroll = 0;
+    }
+
+    /// Toggle pin status for a generation
+    fn toggle_pin(&mut self, gen_id: u32) -> Result<()> {
+        let profile = self.manage_profile.clone();
+        self.config.toggle_pin(&profile, gen_id);
+
+        let is_pinned = self.config.is_pinned(&profile, gen_id);
+        if let Some(gen) = self.generations_for_mut(&profile).iter_mut().find(|g| g.id == gen_id) {
+            gen.is_pinned = is_pinned;
+        }
+
+        self.update_policy_candidates();
         self.config.save()?;
         self.show_flash("Pin status updated", false);
         Ok(())
     }
 
+    /// Mutable generations of a profile (System's if the profile was not found)
+    fn generations_for_mut(&mut self, profile: &ProfileId) -> &mut Vec<Generation> {
+        self.other_profiles
+            .iter_mut()
+            .find(|p| &p.source.id == profile)
+            .map_or(&mut self.system_generations, |p| &mut p.generations)
+    }
+
     /// Prompt for restore confirmation
+    ///
+    /// The current generation can still be switched into one of its specialisations.
     fn prompt_restore(&mut self) -> Result<()> {
-        let generations = if self.manage_profile == ProfileType::System {
-            &self.system_generations
-        } else {
-            self.home_manager_generations.as_ref().unwrap_or(&self.system_generations)
-        };
+        let generations = self.generations_for(&self.manage_profile);
 
-        let gen = match generations.get(self.manage_cursor) {
-            Some(g) if !g.is_current => g,
+        self.restore_specialisation = match generations.get(self.manage_cursor) {
+            Some(g) if !g.is_current => None,
+            Some(g) if !g.specialisations.is_empty() => g.specialisations.first().cloned(),
             _ => {
                 self.show_flash("Cannot restore current generation", true);
                 return Ok(());
             }
         };
 
-        let source = if self.manage_profile == ProfileType::System {
-            &self.system_source
-        } else {
-            self.home_manager_source.as_ref().unwrap_or(&self.system_source)
+        self.show_restore_confirm();
+        Ok(())
+    }
+
+    /// Show the restore confirmation for the generation under the cursor
+    fn show_restore_confirm(&mut self) {
+        let Some(gen) = self.generations_for(&self.manage_profile).get(self.manage_cursor) else {
+            return;
         };
+        let source = self.source_for(&self.manage_profile);
+        let specialisation = self.restore_specialisation.as_deref();
 
         let command = nix::commands::get_restore_command_preview(
             &source.profile_path,
             gen.id,
-            self.manage_profile,
+            specialisation,
+            source.profile_type,
+        );
+
+        let mut message = format!(
+            "Restore {} generation #{}?\n\nDate: {}\nVersion: {}",
+            self.manage_profile.name(),
+            gen.id,
+            gen.formatted_date(),
+            gen.nixos_version.as_deref().unwrap_or("Unknown"),
         );
+        if !gen.specialisations.is_empty() {
+            message.push_str(&format!(
+                "\nSpecialisation: {}  [s] Change",
+                specialisation.unwrap_or("(base configuration)"),
+            ));
+        }
 
         self.popup = PopupState::Confirm {
             title: "Confirm Restore".into(),
-            message: format!(
-                "Restore {} generation #{}?\n\nDate: {}\nVersion: {}",
-                self.manage_profile.as_str(),
-                gen.id,
-                gen.formatted_date(),
-                gen.nixos_version.as_deref().unwrap_or("Unknown"),
-            ),
+            message,
             command,
         };
-
-        Ok(())
     }
 
     /// Prompt for delete confirmation
     fn prompt_delete(&mut self) -> Result<()> {
         let ids: Vec<u32> = if self.manage_selected.is_empty() {
             // Delete single (under cursor)
-            let generations = if self.manage_profile == ProfileType::System {
-                &self.system_generations
-            } else {
-                self.home_manager_generations.as_ref().unwrap_or(&self.system_generations)
-            };
+            let generations = self.generations_for(&self.manage_profile);
 
             match generations.get(self.manage_cursor) {
                 Some(g) if !g.is_current && !g.is_pinned => vec![g.id],
@@ -796,16 +1312,12 @@ impl App {
             return Ok(());
         }
 
-        let source = if self.manage_profile == ProfileType::System {
-            &self.system_source
-        } else {
-            self.home_manager_source.as_ref().unwrap_or(&self.system_source)
-        };
+        let source = self.source_for(&self.manage_profile);
 
         let command = nix::commands::get_delete_command_preview(
             &source.profile_path,
             &ids,
-            self.manage_profile,
+            source.profile_type,
         );
 
         self.popup = PopupState::Confirm {
@@ -833,6 +1345,7 @@ impl App {
 
         self.popup = PopupState::Loading {
             message: "Executing...".into(),
+            progress: None,
         };
 
         let result = if title.contains("Restore") {
@@ -851,7 +1364,7 @@ impl App {
             Ok(cmd_result) if cmd_result.success => {
                 self.popup = PopupState::None;
                 self.show_flash(&cmd_result.message, false);
-                self.refresh_generations()?;
+                self.load_generations();
             }
             Ok(cmd_result) => {
                 self.show_error("Command Failed", &cmd_result.message);
@@ -866,25 +1379,18 @@ impl App {
 
     /// Execute restore action
     fn execute_restore(&mut self) -> Result<CommandResult> {
-        let generations = if self.manage_profile == ProfileType::System {
-            &self.system_generations
-        } else {
-            self.home_manager_generations.as_ref().unwrap_or(&self.system_generations)
-        };
+        let generations = self.generations_for(&self.manage_profile);
 
         let gen = generations.get(self.manage_cursor)
             .ok_or_else(|| anyhow::anyhow!("No generation selected"))?;
 
-        let source = if self.manage_profile == ProfileType::System {
-            &self.system_source
-        } else {
-            self.home_manager_source.as_ref().unwrap_or(&self.system_source)
-        };
+        let source = self.source_for(&self.manage_profile);
 
-        restore_generation(
+        self.backend.restore_generation(
             &source.profile_path,
             gen.id,
-            self.manage_profile,
+            self.restore_specialisation.as_deref(),
+            source.profile_type,
             self.dry_run,
         )
     }
@@ -892,11 +1398,7 @@ impl App {
     /// Execute delete action
     fn execute_delete(&mut self) -> Result<CommandResult> {
         let ids: Vec<u32> = if self.manage_selected.is_empty() {
-            let generations = if self.manage_profile == ProfileType::System {
-                &self.system_generations
-            } else {
-                self.home_manager_generations.as_ref().unwrap_or(&self.system_generations)
-            };
+            let generations = self.generations_for(&self.manage_profile);
 
             generations.get(self.manage_cursor)
                 .map(|g| vec![g.id])
@@ -905,26 +1407,18 @@ impl App {
             self.manage_selected.iter().copied().collect()
         };
 
-        let source = if self.manage_profile == ProfileType::System {
-            &self.system_source
-        } else {
-            self.home_manager_source.as_ref().unwrap_or(&self.system_source)
-        };
+        let source = self.source_for(&self.manage_profile);
 
-        let result = delete_generations(
+        let result = self.backend.delete_generations(
             &source.profile_path,
             &ids,
-            self.manage_profile,
+            source.profile_type,
             self.dry_run,
         )?;
 
         if result.success && !self.dry_run {
             // Start undo countdown
             self.pending_undo = Some(PendingUndo {
-                action: UndoAction::Delete {
-                    profile: self.manage_profile,
-                    generation_ids: ids.clone(),
-                },
                 started_at: Instant::now(),
             });
 
@@ -948,23 +1442,12 @@ impl App {
         Ok(())
     }
 
-    /// Refresh generations from disk
-    fn refresh_generations(&mut self) -> Result<()> {
-        self.system_generations = list_generations(&self.system_source)?;
-        for gen in &mut self.system_generations {
-            gen.is_pinned = self.config.is_system_pinned(gen.id);
-        }
-
-        if let Some(source) = &self.home_manager_source {
-            if let Ok(mut gens) = list_generations(source) {
-                for gen in &mut gens {
-                    gen.is_pinned = self.config.is_home_manager_pinned(gen.id);
-                }
-                self.home_manager_generations = Some(gens);
-            }
-        }
+    /// Re-evaluate the retention policy for the Manage tab profile
+    fn update_policy_candidates(&mut self) {
+        let generations = self.generations_for(&self.manage_profile);
 
-        Ok(())
+        let plan = policy::evaluate(&self.config.retention, generations, chrono::Local::now());
+        self.policy_candidates = plan.to_delete().into_iter().collect();
     }
 
     /// Show an error popup
@@ -979,4 +1462,417 @@ impl App {
     fn show_flash(&mut self, message: &str, is_error: bool) {
         self.flash_message = Some((message.into(), is_error, Instant::now()));
     }
-}
\ No newline at end of file
+}
+
+/// Next entry when cycling through the base configuration and the specialisations
+fn next_specialisation(current: Option<&str>, names: &[String]) -> Option<String> {
+    let next = match current {
+        None => 0,
+        Some(name) => names.iter().position(|n| n == name).map_or(0, |i| i + 1),
+    };
+    names.get(next).cloned()
+}
+
+/// Next update kind filter, ending with no filter
+fn next_update_kind(current: Option<UpdateKind>) -> Option<UpdateKind> {
+    let next = match current {
+        None => 0,
+        Some(kind) => UpdateKind::ALL.iter().position(|k| *k == kind).map_or(0, |i| i + 1),
+    };
+    UpdateKind::ALL.get(next).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CustomProfile, ProfileKind};
+    use crate::nix::fake::{generation, package, FakeBackend};
+    use crate::types::ProfileType;
+
+    fn fixture_app(dry_run: bool) -> App {
+        let (info, backend) = FakeBackend::fixture();
+        let mut app = App::with_backend(info, Config::default(), dry_run, Arc::new(backend));
+        finish_tasks(&mut app);
+        app
+    }
+
+    /// Wait for the background task (if any) and apply its results
+    fn finish_tasks(app: &mut App) {
+        while app.task.is_some() {
+            app.poll_tasks();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\t' => KeyCode::Tab,
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::from(code)).unwrap();
+            finish_tasks(app);
+        }
+    }
+
+    fn ids(gens: &[Generation]) -> Vec<u32> {
+        gens.iter().map(|g| g.id).collect()
+    }
+
+    fn current(gens: &[Generation]) -> Option<u32> {
+        gens.iter().find(|g| g.is_current).map(|g| g.id)
+    }
+
+    #[test]
+    fn test_lists_generations() {
+        let app = fixture_app(false);
+
+        assert_eq!(ids(&app.system_generations), [142, 141, 140]);
+        assert_eq!(current(&app.system_generations), Some(142));
+        assert_eq!(ids(app.generations_for(&ProfileType::HomeManager.into())), [86, 85]);
+    }
+
+    #[test]
+    fn test_diff_generations() {
+        let mut app = fixture_app(false);
+
+        // From: 140 (bottom of the list), To: 142 (top)
+        press(&mut app, "3G\n\t\n");
+
+        assert_eq!((app.diff_from, app.diff_to), (Some(DiffSide::generation(140)), Some(DiffSide::generation(142))));
+        let diff = app.current_diff.as_ref().unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "ripgrep");
+        assert!(diff.removed.is_empty());
+
+        let linux = diff.updated.iter().find(|u| u.name == "linux").unwrap();
+        assert!(linux.is_kernel);
+        assert_eq!((linux.old_version.as_str(), linux.new_version.as_str()), ("6.6.50", "6.6.52"));
+    }
+
+    #[test]
+    fn test_delete_generations() {
+        let mut app = fixture_app(false);
+
+        // Select 141 and 140, delete, confirm
+        press(&mut app, "4j j dy");
+
+        assert_eq!(ids(&app.system_generations), [142]);
+        assert!(app.manage_selected.is_empty());
+        assert_eq!(ids(app.generations_for(&ProfileType::HomeManager.into())), [86, 85]);
+    }
+
+    #[test]
+    fn test_diff_home_manager() {
+        let mut app = fixture_app(false);
+
+        // Diff tab, next profile: Home-Manager 85 -> 86
+        press(&mut app, "3pG\n\t\n");
+        assert_eq!(app.diff_profile, ProfileType::HomeManager.into());
+        let diff = app.current_diff.as_ref().unwrap();
+        assert_eq!(diff.updated.len(), 1);
+        assert_eq!(diff.updated[0].name, "git");
+        assert_eq!((diff.updated[0].old_version.as_str(), diff.updated[0].new_version.as_str()), ("2.44.0", "2.45.1"));
+        assert_eq!(diff.updated[0].kind, UpdateKind::Minor);
+
+        // The filter cycles through the update kinds and back to all
+        press(&mut app, "ff");
+        assert_eq!(app.diff_kind_filter, Some(UpdateKind::Minor));
+        press(&mut app, "fffff");
+        assert_eq!(app.diff_kind_filter, None);
+
+        // Switching the profile drops the selection
+        press(&mut app, "p");
+        assert_eq!(app.diff_profile, ProfileType::NixProfile.into());
+        assert!(app.diff_sides().is_none() && app.current_diff.is_none());
+    }
+
+    #[test]
+    fn test_full_closure_diff() {
+        let mut app = fixture_app(false);
+
+        // #141 -> #142: openssl changed, so firefox 122.0 was rebuilt
+        press(&mut app, "3j\n\t\n");
+        assert!(app.current_diff.as_ref().unwrap().rebuilt.is_empty());
+        press(&mut app, "m");
+        assert!(app.diff_full_closure);
+        let diff = app.current_diff.as_ref().unwrap();
+        assert_eq!(diff.rebuilt.len(), 1);
+        assert_eq!((diff.rebuilt[0].name.as_str(), diff.rebuilt[0].version.as_str()), ("firefox", "122.0"));
+        assert_eq!(diff.rebuilt[0].caused_by, ["openssl 3.0.14"]);
+        assert_eq!(diff.updated.len(), 2); // linux and openssl
+
+        // Back to the package view
+        press(&mut app, "m");
+        assert!(app.current_diff.as_ref().unwrap().rebuilt.is_empty());
+    }
+
+    #[test]
+    fn test_diff_path() {
+        let mut app = fixture_app(false);
+
+        // The input starts at ./result; anything outside the store is rejected
+        press(&mut app, "3o");
+        assert!(matches!(&app.popup, PopupState::PathInput { input } if input == "./result"));
+        press(&mut app, "-x\n");
+        assert!(matches!(&app.popup, PopupState::Error { title, .. } if title == "Invalid Path"));
+        assert!(app.diff_sides().is_none());
+        app.popup = PopupState::None;
+
+        // With no From side, the path becomes the To side and is compared
+        // with the current generation
+        let path = app.system_source.generation_path(140);
+        press(&mut app, "o");
+        for _ in 0.."./result".len() {
+            app.handle_key(KeyEvent::from(KeyCode::Backspace)).unwrap();
+        }
+        press(&mut app, &format!("{}\n", path.display()));
+        assert_eq!(app.diff_focus, 1);
+        assert_eq!(app.diff_sides(), Some((DiffSide::generation(142), DiffSide::Path(path))));
+        let diff = app.current_diff.as_ref().unwrap();
+        assert_eq!(diff.removed.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["ripgrep"]);
+        assert_eq!(diff.closure_change, Some(-1024));
+
+        press(&mut app, "z");
+        assert_eq!(app.diff_sort, DiffSort::Size);
+
+        // Paths have no specialisations
+        press(&mut app, "s");
+        assert!(matches!(app.diff_to, Some(DiffSide::Path(_))));
+    }
+
+    #[test]
+    fn test_specialisations() {
+        let mut app = fixture_app(false);
+        assert_eq!(app.system_generations[0].specialisations, ["gaming"]);
+
+        // Packages of #142, then of its specialisation and back
+        press(&mut app, "\ns");
+        assert_eq!(app.packages_specialisation.as_deref(), Some("gaming"));
+        assert!(app.packages_list.iter().any(|p| p.name == "steam"));
+        press(&mut app, "s");
+        assert_eq!(app.packages_specialisation, None);
+        assert_eq!(app.packages_list.len(), 4);
+
+        // Diff: base configuration of #142 against its specialisation
+        press(&mut app, "3\n\t\ns");
+        let diff = app.current_diff.as_ref().unwrap();
+        let added: Vec<&str> = diff.added.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(added, ["steam"]);
+        assert!(diff.updated.is_empty());
+
+        // The current generation can be switched into its specialisation
+        press(&mut app, "4r");
+        let PopupState::Confirm { command, .. } = &app.popup else {
+            panic!("expected restore prompt");
+        };
+        assert!(command.contains("system-142-link/specialisation/gaming/bin/switch-to-configuration"));
+        press(&mut app, "s");
+        assert_eq!(app.restore_specialisation.as_deref(), Some("gaming")); // Base is already active
+        press(&mut app, "y");
+        assert!(matches!(app.flash_message.as_ref(), Some((message, false, _)) if message.contains("142")));
+    }
+
+    #[test]
+    fn test_restore_generation() {
+        let mut app = fixture_app(false);
+
+        press(&mut app, "4jry");
+
+        assert_eq!(current(&app.system_generations), Some(141));
+    }
+
+    #[test]
+    fn test_nix_profile() {
+        let mut app = fixture_app(false);
+        assert_eq!(
+            app.profiles(),
+            [ProfileType::System, ProfileType::HomeManager, ProfileType::NixProfile].map(ProfileId::from)
+        );
+
+        // Overview: focus the nix profile and open generation 4
+        press(&mut app, "\t\t\n");
+        assert_eq!(app.active_tab, Tab::Packages);
+        assert_eq!(app.packages_profile, ProfileType::NixProfile.into());
+        assert_eq!(app.packages_gen_id, Some(4));
+        assert_eq!(app.packages_list[0].name, "ripgrep");
+
+        // Manage: switch to the nix profile and restore generation 3
+        press(&mut app, "4\t\tjry");
+        assert_eq!(current(app.generations_for(&ProfileType::NixProfile.into())), Some(3));
+        assert_eq!(current(&app.system_generations), Some(142));
+    }
+
+    #[test]
+    fn test_custom_profile() {
+        let (info, backend) = FakeBackend::fixture();
+        let devtools = CustomProfile {
+            name: "devtools".into(),
+            path: "/nix/var/nix/profiles/per-user/alice/devtools".into(),
+            kind: ProfileKind::NixEnv,
+        };
+        let missing = CustomProfile {
+            name: "missing".into(),
+            path: "/nix/var/nix/profiles/missing".into(),
+            kind: ProfileKind::NixEnv,
+        };
+        let source = GenerationSource::custom(&devtools);
+        let backend = backend
+            .with_generation(&source, generation(1, false), vec![package("jq", "1.7")])
+            .with_generation(&source, generation(2, true), vec![package("jq", "1.7.1")]);
+        let config = Config { profiles: vec![devtools, missing], ..Config::default() };
+
+        let mut app = App::with_backend(info, config, false, Arc::new(backend));
+        finish_tasks(&mut app);
+        let id = ProfileId::Custom("devtools".into());
+        assert_eq!(app.profiles().last(), Some(&id));
+        assert!(app.flash_message.as_ref().is_some_and(|(msg, is_error, _)| *is_error && msg.contains("missing")));
+
+        // Manage: switch to the custom profile and restore generation 1
+        press(&mut app, "4			jry");
+        assert_eq!(app.manage_profile, id);
+        assert_eq!(current(app.generations_for(&id)), Some(1));
+        assert_eq!(current(&app.system_generations), Some(142));
+    }
+
+    #[test]
+    fn test_dry_run_changes_nothing() {
+        let mut app = fixture_app(true);
+
+        press(&mut app, "4j dy");
+        press(&mut app, "ry");
+
+        assert_eq!(ids(&app.system_generations), [142, 141, 140]);
+        assert_eq!(current(&app.system_generations), Some(142));
+    }
+
+    #[test]
+    fn test_loading_progress_and_cancel() {
+        let (info, backend) = FakeBackend::fixture();
+        let mut app = App::with_backend(info, Config::default(), false, Arc::new(backend));
+        assert!(matches!(app.popup, PopupState::Loading { .. }));
+        finish_tasks(&mut app);
+        assert!(matches!(app.popup, PopupState::None));
+        assert_eq!(app.profiles().len(), 3);
+
+        // Start a diff and cancel it before its result is applied
+        press(&mut app, "3G\n\t");
+        app.handle_key(KeyEvent::from(KeyCode::Enter)).unwrap();
+        assert_eq!(app.state(), AppState::Loading);
+        app.handle_key(KeyEvent::from(KeyCode::Esc)).unwrap();
+
+        assert!(app.task.is_none());
+        assert_eq!(app.state(), AppState::Normal);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        app.poll_tasks();
+        assert!(app.current_diff.is_none());
+
+        // Loading generations reports progress per generation
+        let task = tasks::load_generations(Arc::clone(&app.backend), vec![app.system_source.clone()], false);
+        let mut steps = Vec::new();
+        while !steps.contains(&None) {
+            steps.extend(task.poll().unwrap().into_iter().filter_map(|event| match event {
+                TaskEvent::Progress(p) => Some(Some((p.done, p.total))),
+                TaskEvent::Done => Some(None),
+                _ => None,
+            }));
+        }
+        assert_eq!(steps, [Some((0, 3)), Some((1, 3)), Some((2, 3)), None]);
+    }
+
+    #[test]
+    fn test_why_runs_in_background() {
+        let mut app = fixture_app(false);
+
+        // Packages of #142, why is firefox there?
+        press(&mut app, "\n");
+        let firefox = app.packages_list.iter().position(|p| p.name == "firefox").unwrap();
+        app.packages_selected = firefox;
+        app.handle_key(KeyEvent::from(KeyCode::Char('w'))).unwrap();
+        assert_eq!(app.state(), AppState::Loading);
+        finish_tasks(&mut app);
+        let PopupState::Why { title, tree, .. } = &app.popup else {
+            panic!("expected why tree");
+        };
+        assert_eq!(title, "Why firefox is in #142");
+        assert!(tree.children.iter().all(|c| c.is_target));
+
+        // Esc cancels the trace before the tree opens
+        app.popup = PopupState::None;
+        app.handle_key(KeyEvent::from(KeyCode::Char('w'))).unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Esc)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        app.poll_tasks();
+        assert_eq!(app.state(), AppState::Normal);
+    }
+
+    #[test]
+    fn test_history_runs_in_background() {
+        let mut app = fixture_app(false);
+
+        // Packages of #142 are kept for the history
+        press(&mut app, "\n");
+        let gen = app.system_generations[0].clone();
+        let cached = app.package_cache.lock().unwrap().packages(&FakeBackend::new(), &app.system_source, &gen).len();
+        assert_eq!(cached, 4);
+
+        let firefox = app.packages_list.iter().position(|p| p.name == "firefox").unwrap();
+        app.packages_selected = firefox;
+        app.handle_key(KeyEvent::from(KeyCode::Enter)).unwrap();
+        assert_eq!(app.state(), AppState::Loading);
+        finish_tasks(&mut app);
+        let PopupState::History { package, entries, .. } = &app.popup else {
+            panic!("expected history");
+        };
+        assert_eq!(package, "firefox");
+        let changes: Vec<(u32, &str)> = entries.iter().map(|e| (e.gen_id, e.change_label())).collect();
+        assert_eq!(changes, [(141, "upgraded"), (140, "added")]);
+
+        // Progress is reported per generation
+        let task = tasks::load_history(
+            Arc::clone(&app.backend),
+            app.system_source.clone(),
+            app.system_generations.clone(),
+            "firefox".into(),
+            Arc::clone(&app.package_cache),
+        );
+        let mut steps = Vec::new();
+        while !steps.contains(&None) {
+            steps.extend(task.poll().unwrap().into_iter().filter_map(|event| match event {
+                TaskEvent::Progress(p) => Some(Some((p.done, p.total))),
+                TaskEvent::Done => Some(None),
+                _ => None,
+            }));
+        }
+        assert_eq!(steps, [Some((0, 3)), Some((1, 3)), Some((2, 3)), None]);
+    }
+
+    #[test]
+    fn test_reload_after_cancelled_load() {
+        let (info, backend) = FakeBackend::fixture();
+        let mut app = App::with_backend(info, Config::default(), false, Arc::new(backend));
+        app.handle_key(KeyEvent::from(KeyCode::Esc)).unwrap();
+        assert!(app.system_generations.is_empty());
+        assert!(matches!(&app.flash_message, Some((message, true, _)) if message.contains("Ctrl+R")));
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)).unwrap();
+        assert_eq!(app.state(), AppState::Loading);
+        finish_tasks(&mut app);
+        assert_eq!(ids(&app.system_generations), [142, 141, 140]);
+        assert_eq!(app.profiles().len(), 3);
+    }
+
+    #[test]
+    fn test_toggle_closure_count() {
+        let mut app = fixture_app(false);
+        assert_eq!(app.system_generations[0].closure_count, None);
+
+        // Counts are only read once the option is enabled
+        app.config.display.show_closure_count = true;
+        app.load_generations();
+        finish_tasks(&mut app);
+        assert_eq!(app.system_generations[0].closure_count, Some(5));
+        assert_eq!(app.generations_for(&ProfileType::HomeManager.into())[0].closure_count, Some(2));
+    }
+}
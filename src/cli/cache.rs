@@ -0,0 +1,35 @@
+//! `nixhist cache` - manage the metadata cache
+//!
+//! Usage: nixhist cache clear
+//!
+//! Closure sizes, versions and package lists are cached per store path
+//! under ~/.cache/nixhist. `--no-cache` bypasses the cache for one run.
+
+use crate::nix::cache;
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+/// Arguments of the cache subcommand
+#[derive(Debug, Clone, Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub action: CacheAction,
+}
+
+/// Cache operations
+#[derive(Debug, Clone, Subcommand)]
+pub enum CacheAction {
+    /// Delete all cached metadata
+    Clear,
+}
+
+/// Run the cache subcommand
+pub fn run(args: &CacheArgs) -> Result<()> {
+    match args.action {
+        CacheAction::Clear => match cache::clear()? {
+            Some(dir) => println!("Removed {}", dir.display()),
+            None => println!("Cache is already empty"),
+        },
+    }
+    Ok(())
+}
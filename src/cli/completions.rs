@@ -0,0 +1,164 @@
+//! `nixhist completions` and `nixhist manpage` - generated from the CLI definition
+//!
+//! Usage: nixhist completions bash|zsh|fish|elvish|powershell
+//!        nixhist manpage > nixhist.1
+//!
+//! Completions are dynamic: the script calls back into `nixhist` with
+//! `COMPLETE=<shell>` set (see `clap_complete::CompleteEnv`), so generation
+//! arguments of `diff`, `packages`, `pin` and `unpin` complete to the IDs
+//! read from the profile directory, for the `--profile` on the command line.
+
+use crate::cli::{self, Cli, GlobalArgs};
+use crate::config::Config;
+use crate::nix::list_generation_ids;
+use anyhow::{Context, Result};
+use clap::{Args, CommandFactory, Parser};
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+use std::io::Write;
+
+/// Environment variable that switches `nixhist` into completion mode
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Global options that decide which profile's generations are completed
+const PROFILE_OPTIONS: [&str; 5] = ["-p", "--profile", "--system-profile", "--nix-profile", "--config"];
+
+/// Arguments of the completions subcommand
+#[derive(Debug, Clone, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+/// Run the completions subcommand
+pub fn run_completions(args: &CompletionsArgs) -> Result<()> {
+    let script = completion_script(args.shell)?;
+    std::io::stdout()
+        .write_all(script.as_bytes())
+        .context("Failed to write completions")
+}
+
+/// Run the manpage subcommand
+pub fn run_manpage() -> Result<()> {
+    let man = clap_mangen::Man::new(Cli::command());
+    man.render(&mut std::io::stdout())
+        .context("Failed to write man page")
+}
+
+/// Registration script for a shell (the same as `COMPLETE=<shell> nixhist`)
+fn completion_script(shell: Shell) -> Result<String> {
+    let cmd = Cli::command();
+    let name = cmd.get_name();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .with_context(|| format!("No dynamic completions for {}", shell))?;
+
+    let mut buf = Vec::new();
+    completer
+        .write_registration(COMPLETE_VAR, name, name, name, &mut buf)
+        .context("Failed to generate completions")?;
+    String::from_utf8(buf).context("Completion script is not valid UTF-8")
+}
+
+/// Candidates for a generation argument: IDs (newest first) and aliases
+///
+/// Errors are silent: a completer must never print to the prompt.
+pub fn generation_candidates() -> Vec<CompletionCandidate> {
+    // In completion mode the words being completed follow `--`
+    let words = std::env::args().skip_while(|arg| arg != "--").skip(2);
+    let global = completed_global_args(words);
+    if let Some(path) = &global.config {
+        Config::set_path_override(path.clone());
+    }
+
+    let ids = global
+        .system_info()
+        .and_then(|info| cli::resolve_source(&info, &global.config()?, &global.profile()))
+        .and_then(|source| list_generation_ids(&source))
+        .unwrap_or_default();
+
+    ids.iter()
+        .map(u32::to_string)
+        .chain(["current", "previous", "booted"].map(String::from))
+        .enumerate()
+        .map(|(order, value)| CompletionCandidate::new(value).display_order(Some(order)))
+        .collect()
+}
+
+/// Profile options among the words typed so far (everything else is ignored)
+fn completed_global_args(mut words: impl Iterator<Item = String>) -> GlobalArgs {
+    let mut args = vec!["nixhist".to_string()];
+    while let Some(word) = words.next() {
+        let option = word.split('=').next().unwrap_or_default();
+        if word == "--no-home-manager" {
+            args.push(word);
+        } else if PROFILE_OPTIONS.contains(&option) {
+            let has_value = word.contains('=');
+            args.push(word);
+            if !has_value {
+                args.extend(words.next());
+            }
+        }
+    }
+
+    Cli::try_parse_from(&args)
+        .or_else(|_| Cli::try_parse_from(["nixhist"]))
+        .map(|cli| cli.global)
+        .expect("no arguments always parse")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ProfileId, ProfileType};
+    use std::path::PathBuf;
+
+    fn words(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn test_completion_scripts() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Elvish, Shell::PowerShell] {
+            let script = completion_script(shell).unwrap();
+            assert!(script.contains(COMPLETE_VAR), "{}", shell);
+            assert!(script.contains("nixhist"), "{}", shell);
+        }
+        assert!(completion_script(Shell::Zsh).unwrap().starts_with("#compdef nixhist"));
+    }
+
+    #[test]
+    fn test_generation_arguments_have_completer() {
+        use clap_complete::ArgValueCandidates;
+
+        let cmd = Cli::command();
+        for (subcommand, args) in [
+            ("diff", &["from", "to"][..]),
+            ("packages", &["generation"]),
+            ("pin", &["generation"]),
+            ("unpin", &["generation"]),
+        ] {
+            let sub = cmd.find_subcommand(subcommand).unwrap();
+            for arg in args {
+                let arg = sub.get_arguments().find(|a| a.get_id() == *arg).unwrap();
+                assert!(arg.get::<ArgValueCandidates>().is_some(), "{} {}", subcommand, arg.get_id());
+            }
+        }
+    }
+
+    #[test]
+    fn test_completed_global_args() {
+        let global = completed_global_args(words("-p home-manager diff 14"));
+        assert_eq!(global.profile(), ProfileId::Builtin(ProfileType::HomeManager));
+
+        let global = completed_global_args(words("--system-profile=/tmp/system --no-home-manager pin"));
+        assert_eq!(global.system_profile, Some(PathBuf::from("/tmp/system")));
+        assert!(global.no_home_manager);
+
+        // Incomplete or unrelated options fall back to the defaults
+        let global = completed_global_args(words("--bogus diff -p"));
+        assert_eq!(global.profile(), ProfileId::SYSTEM);
+    }
+}
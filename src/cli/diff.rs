@@ -0,0 +1,407 @@
+//! `nixhist diff` - compare the packages of two generations
+//!
+//! Usage: nixhist diff [FROM] <TO> [--profile system|home-manager] [--sort name|size] [--kind KIND]
+//!                     [--closure] [--json]
+//!
+//! Either side may be a store path or a link to one, e.g. `nixhist diff
+//! ./result` previews a build against the current generation before
+//! switching to it.
+//!
+//! Exit codes: 0 = no kernel or security updates, 1 = error,
+//! otherwise a combination of `EXIT_KERNEL` and `EXIT_SECURITY`.
+
+use crate::cli::completions::generation_candidates;
+use crate::cli::{self, GenerationSpec, GlobalArgs};
+use crate::nix::closure::closure_diff;
+use crate::nix::{GenerationSource, NixBackend};
+use crate::types::{format_size_with_change, DiffSide, DiffSort, Generation, GenerationDiff, ProfileId, UpdateKind};
+use anyhow::{Context, Result};
+use clap::Args;
+use clap_complete::ArgValueCandidates;
+use crossterm::style::Stylize;
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+/// Exit code bit set when the diff contains a kernel update
+pub const EXIT_KERNEL: i32 = 2;
+
+/// Exit code bit set when the diff contains a security-related update
+pub const EXIT_SECURITY: i32 = 4;
+
+/// Exit code reference appended to `nixhist diff --help`
+pub const EXIT_CODES_HELP: &str = "\
+EXIT CODES:
+    0    No kernel or security updates
+    1    Error
+    2    Kernel update
+    4    Security-related update
+    6    Kernel and security updates";
+
+/// One side of a diff on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffTarget {
+    Generation(GenerationSpec),
+    /// A store path or a link to one, such as `./result`
+    Path(PathBuf),
+}
+
+impl DiffTarget {
+    /// Parse a generation spec, or a path if the value contains a `/`
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.contains('/') {
+            Ok(DiffTarget::Path(PathBuf::from(s)))
+        } else {
+            GenerationSpec::parse(s).map(DiffTarget::Generation)
+        }
+    }
+
+    /// Find the generation or store path this target refers to
+    pub fn resolve(&self, backend: &dyn NixBackend, generations: &[Generation]) -> Result<DiffSide> {
+        match self {
+            DiffTarget::Generation(spec) => Ok(DiffSide::generation(spec.resolve(generations)?.id)),
+            DiffTarget::Path(path) => backend
+                .store_path(path)
+                .map(|store_path| DiffSide::Path(store_path.into()))
+                .with_context(|| format!("{} is not a store path or a link to one", path.display())),
+        }
+    }
+}
+
+/// Arguments of the diff subcommand
+#[derive(Debug, Clone, Args)]
+pub struct DiffArgs {
+    /// Old generation (an ID, current, previous or booted) or store path
+    #[arg(value_parser = DiffTarget::parse, add = ArgValueCandidates::new(generation_candidates))]
+    pub from: DiffTarget,
+
+    /// New generation or store path; a single argument is compared with the current generation
+    #[arg(value_parser = DiffTarget::parse, add = ArgValueCandidates::new(generation_candidates))]
+    pub to: Option<DiffTarget>,
+
+    /// Order of the packages within each section
+    #[arg(long, value_enum, default_value_t = DiffSort::Name)]
+    pub sort: DiffSort,
+
+    /// Only list updates of this kind
+    #[arg(long, value_enum)]
+    pub kind: Option<UpdateKind>,
+
+    /// Compare every store path of the closures, listing same-version rebuilds
+    #[arg(long)]
+    pub closure: bool,
+
+    /// Print the diff as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// JSON representation of one side of a diff: a generation ID or a store path
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SideOutput {
+    Generation(u32),
+    Path(String),
+}
+
+impl From<&DiffSide> for SideOutput {
+    fn from(side: &DiffSide) -> Self {
+        match side {
+            DiffSide::Generation { id, .. } => SideOutput::Generation(*id),
+            DiffSide::Path(path) => SideOutput::Path(path.display().to_string()),
+        }
+    }
+}
+
+/// JSON representation of a diff
+#[derive(Serialize)]
+struct DiffOutput<'a> {
+    profile: ProfileId,
+    from: SideOutput,
+    to: SideOutput,
+    has_kernel_update: bool,
+    has_security_update: bool,
+    #[serde(flatten)]
+    diff: &'a GenerationDiff,
+}
+
+/// Run the diff subcommand, returning the process exit code
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &DiffArgs) -> Result<i32> {
+    let (source, generations) = cli::load_generations(global, backend)?;
+
+    let (from, to) = match &args.to {
+        Some(to) => (args.from.resolve(backend, &generations)?, to.resolve(backend, &generations)?),
+        None => (
+            DiffTarget::Generation(GenerationSpec::Current).resolve(backend, &generations)?,
+            args.from.resolve(backend, &generations)?,
+        ),
+    };
+
+    let mut diff = if args.closure {
+        load_closure_diff(backend, &source, &from, &to)?
+    } else {
+        load_diff(backend, &source, &from, &to)?
+    };
+    diff.sort(args.sort);
+    // The exit code and flags describe the whole diff, not just the listed updates
+    let code = exit_code(&diff);
+    let has_kernel_update = diff.has_kernel_update();
+    let has_security_update = diff.has_security_update();
+    if let Some(kind) = args.kind {
+        diff.updated.retain(|u| u.kind == kind);
+    }
+
+    if args.json {
+        let output = DiffOutput {
+            profile: global.profile(),
+            from: SideOutput::from(&from),
+            to: SideOutput::from(&to),
+            has_kernel_update,
+            has_security_update,
+            diff: &diff,
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output).context("Failed to serialize diff")?
+        );
+    } else {
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("{}", format_text(&diff, &from, &to, color));
+    }
+
+    Ok(code)
+}
+
+/// Compare the packages of two generations of a profile (or store paths)
+pub fn load_diff(
+    backend: &dyn NixBackend,
+    source: &GenerationSource,
+    from: &DiffSide,
+    to: &DiffSide,
+) -> Result<GenerationDiff> {
+    let from_packages = backend
+        .get_packages(&source.side_path(from))
+        .with_context(|| format!("Failed to read packages of {}", from))?;
+    let to_packages = backend
+        .get_packages(&source.side_path(to))
+        .with_context(|| format!("Failed to read packages of {}", to))?;
+
+    // Sizes are only informational, so a diff without them is still useful
+    let from_size = backend.closure_size(&source.side_path(from)).unwrap_or(0);
+    let to_size = backend.closure_size(&source.side_path(to)).unwrap_or(0);
+
+    Ok(GenerationDiff::calculate(&from_packages, &to_packages).with_closure_sizes(from_size, to_size))
+}
+
+/// Compare the full closures of two generations of a profile (or store paths)
+fn load_closure_diff(
+    backend: &dyn NixBackend,
+    source: &GenerationSource,
+    from: &DiffSide,
+    to: &DiffSide,
+) -> Result<GenerationDiff> {
+    let from_graph = backend
+        .closure_graph(&source.side_path(from))
+        .with_context(|| format!("Failed to read the closure of {}", from))?;
+    let to_graph = backend
+        .closure_graph(&source.side_path(to))
+        .with_context(|| format!("Failed to read the closure of {}", to))?;
+
+    Ok(closure_diff(&from_graph, &to_graph))
+}
+
+/// Exit code describing the kind of updates in a diff
+pub fn exit_code(diff: &GenerationDiff) -> i32 {
+    let mut code = 0;
+    if diff.has_kernel_update() {
+        code |= EXIT_KERNEL;
+    }
+    if diff.has_security_update() {
+        code |= EXIT_SECURITY;
+    }
+    code
+}
+
+/// Format a diff as (optionally colored) text, mirroring the Diff tab
+fn format_text(diff: &GenerationDiff, from: &DiffSide, to: &DiffSide, color: bool) -> String {
+    let paint = |text: String, style: fn(String) -> crossterm::style::StyledContent<String>| {
+        if color {
+            style(text).to_string()
+        } else {
+            text
+        }
+    };
+
+    let mut out = String::new();
+    let header = match (from, to) {
+        (DiffSide::Generation { .. }, DiffSide::Generation { .. }) => format!("Generation {} → {}", from, to),
+        _ => format!("{} → {}", from, to),
+    };
+    out.push_str(&paint(header, |s| s.bold()));
+    out.push('\n');
+    if diff.is_empty() {
+        out.push_str("No package changes\n");
+        return out;
+    }
+    out.push_str(&diff.summary());
+    out.push_str("\n\n");
+
+    if !diff.added.is_empty() {
+        out.push_str(&paint(format!("Added ({})", diff.added.len()), |s| s.green().bold()));
+        out.push('\n');
+        for pkg in &diff.added {
+            out.push_str(&paint(" + ".into(), |s| s.green()));
+            out.push_str(&format!("{} {}", pkg.name, pkg.version));
+            if pkg.size > 0 {
+                out.push_str(&paint(format!("  {}", format_size_with_change(pkg.size, pkg.size as i64)), |s| s.dim()));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !diff.removed.is_empty() {
+        out.push_str(&paint(format!("Removed ({})", diff.removed.len()), |s| s.red().bold()));
+        out.push('\n');
+        for pkg in &diff.removed {
+            out.push_str(&paint(" - ".into(), |s| s.red()));
+            out.push_str(&format!("{} {}", pkg.name, pkg.version));
+            if pkg.size > 0 {
+                out.push_str(&paint(format!("  {}", format_size_with_change(pkg.size, -(pkg.size as i64))), |s| s.dim()));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !diff.updated.is_empty() {
+        out.push_str(&paint(format!("Updated ({})", diff.updated.len()), |s| s.yellow().bold()));
+        out.push('\n');
+        for upd in &diff.updated {
+            out.push_str(&paint(" ~ ".into(), |s| s.yellow()));
+            out.push_str(&format!("{} {} → {}", upd.name, upd.old_version, upd.new_version));
+            out.push_str(&paint(format!(" {}", upd.kind.label()), kind_style(upd.kind)));
+            if upd.old_size > 0 || upd.new_size > 0 {
+                out.push_str(&paint(format!("  {}", format_size_with_change(upd.new_size, upd.size_change())), |s| s.dim()));
+            }
+            if upd.is_kernel {
+                out.push_str(&paint(" ⚠ Kernel".into(), |s| s.magenta().bold()));
+            } else if upd.is_security {
+                out.push_str(&paint(" ⚠ Security".into(), |s| s.magenta().bold()));
+            }
+            out.push('\n');
+        }
+    }
+
+    if !diff.rebuilt.is_empty() {
+        if !diff.updated.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&paint(format!("Rebuilt ({})", diff.rebuilt.len()), |s| s.bold()));
+        out.push('\n');
+        for rebuild in &diff.rebuilt {
+            let cause = if rebuild.caused_by.is_empty() {
+                "build-time change".to_string()
+            } else {
+                rebuild.caused_by.join(", ")
+            };
+            out.push_str(&format!(" ⟳ {} {}", rebuild.name, rebuild.version));
+            out.push_str(&paint(format!("  ← {}", cause), |s| s.dim()));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Text color of an update kind
+fn kind_style(kind: UpdateKind) -> fn(String) -> crossterm::style::StyledContent<String> {
+    match kind {
+        UpdateKind::Major => |s| s.yellow(),
+        UpdateKind::Minor => |s| s.cyan(),
+        UpdateKind::Patch => |s| s.blue(),
+        UpdateKind::Downgrade => |s| s.red().bold(),
+        UpdateKind::PreRelease => |s| s.magenta(),
+        UpdateKind::UnstableDate => |s| s.dark_cyan(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::fake::FakeBackend;
+    use crate::types::Package;
+
+    fn pkg(name: &str, version: &str) -> Package {
+        Package { name: name.into(), version: version.into(), size: 0, attr_path: None, outputs: Vec::new() }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let old = vec![pkg("linux", "6.6.51"), pkg("openssl", "3.0.13"), pkg("foo", "1.0")];
+
+        let only_foo = vec![pkg("linux", "6.6.51"), pkg("openssl", "3.0.13"), pkg("foo", "1.1")];
+        assert_eq!(exit_code(&GenerationDiff::calculate(&old, &only_foo)), 0);
+
+        let security = vec![pkg("linux", "6.6.51"), pkg("openssl", "3.0.14"), pkg("foo", "1.0")];
+        assert_eq!(exit_code(&GenerationDiff::calculate(&old, &security)), EXIT_SECURITY);
+
+        let both = vec![pkg("linux", "6.6.52"), pkg("openssl", "3.0.14"), pkg("foo", "1.0")];
+        assert_eq!(exit_code(&GenerationDiff::calculate(&old, &both)), EXIT_KERNEL | EXIT_SECURITY);
+    }
+
+    #[test]
+    fn test_format_text_plain() {
+        let diff = GenerationDiff::calculate(&[pkg("foo", "1.0")], &[pkg("foo", "1.1"), pkg("bar", "2.0")]);
+        let text = format_text(&diff, &DiffSide::generation(140), &DiffSide::generation(142), false);
+        assert!(text.contains("Generation #140 → #142"));
+        assert!(text.contains(" + bar 2.0"));
+        assert!(text.contains(" ~ foo 1.0 → 1.1 minor"));
+        assert!(!text.contains('\x1b'));
+
+        let same = GenerationDiff::calculate(&[pkg("foo", "1.0")], &[pkg("foo", "1.0")]);
+        let text = format_text(&same, &DiffSide::generation(140), &DiffSide::generation(141), false);
+        assert!(text.contains("No package changes"));
+
+        let result = DiffSide::Path("/nix/store/abc123-nixos-system-host-24.11".into());
+        let text = format_text(&same, &DiffSide::generation(142), &result, false);
+        assert!(text.starts_with("#142 → /nix/store/abc123-nixos-system-host-24.11\n"));
+    }
+
+    #[test]
+    fn test_diff_target() {
+        assert_eq!(DiffTarget::parse("140").unwrap(), DiffTarget::Generation(GenerationSpec::Id(140)));
+        assert_eq!(DiffTarget::parse("current").unwrap(), DiffTarget::Generation(GenerationSpec::Current));
+        assert_eq!(DiffTarget::parse("./result").unwrap(), DiffTarget::Path("./result".into()));
+        assert!(DiffTarget::parse("result").is_err());
+
+        let (_, backend) = FakeBackend::fixture();
+        let err = DiffTarget::Path("/nonexistent/result".into()).resolve(&backend, &[]).unwrap_err();
+        assert!(err.to_string().contains("not a store path"));
+    }
+
+    #[test]
+    fn test_load_diff_through_backend() {
+        let (info, backend) = FakeBackend::fixture();
+        let source = GenerationSource::system(&info);
+        let generations = backend.list_generations(&source).unwrap();
+
+        // A single path argument is compared with the current generation
+        let result = DiffTarget::Path(source.generation_path(140));
+        let from = DiffTarget::Generation(GenerationSpec::Current).resolve(&backend, &generations).unwrap();
+        let to = result.resolve(&backend, &generations).unwrap();
+        assert_eq!(from, DiffSide::generation(142));
+        assert_eq!(to, DiffSide::Path(source.generation_path(140)));
+
+        let diff = load_diff(&backend, &source, &from, &to).unwrap();
+        assert_eq!(diff.removed.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["ripgrep"]);
+        assert_eq!(diff.closure_change, Some(-1024));
+        assert_eq!(exit_code(&diff), EXIT_KERNEL | EXIT_SECURITY);
+
+        // The full-closure diff reads the closures from the backend too
+        let from = DiffSide::generation(141);
+        let closure = load_closure_diff(&backend, &source, &from, &DiffSide::generation(142)).unwrap();
+        assert_eq!(closure.added.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["ripgrep"]);
+        assert!(closure.updated.iter().any(|u| u.name == "openssl"));
+    }
+}
@@ -0,0 +1,217 @@
+//! `nixhist gc` - delete generations according to the retention policy
+//!
+//! Usage: nixhist gc [--policy FILE] [--keep-last N] [--keep-days D]
+//!                   [--keep-weekly N] [--keep-monthly N] [--yes]
+//!
+//! Rules come from `[retention]` in config.toml (or `--policy FILE`),
+//! with `--keep-*` options overriding individual rules.
+
+use crate::cli::{self, GlobalArgs};
+use crate::config::RetentionPolicy;
+use crate::nix::{GenerationSource, NixBackend};
+use crate::policy::{self, RetentionPlan};
+use crate::types::Generation;
+use anyhow::{Context, Result};
+use clap::Args;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// Arguments of the gc subcommand
+#[derive(Debug, Clone, Args)]
+pub struct GcArgs {
+    /// Read retention rules from this TOML file instead of config.toml
+    #[arg(long, value_name = "FILE")]
+    pub policy: Option<PathBuf>,
+
+    /// Keep the N newest generations
+    #[arg(long, value_name = "N")]
+    pub keep_last: Option<usize>,
+
+    /// Keep generations newer than D days
+    #[arg(long, value_name = "D")]
+    pub keep_days: Option<u32>,
+
+    /// Keep the newest generation of each of the last N weeks
+    #[arg(long, value_name = "N")]
+    pub keep_weekly: Option<usize>,
+
+    /// Keep the newest generation of each of the last N months
+    #[arg(long, value_name = "N")]
+    pub keep_monthly: Option<usize>,
+
+    /// Delete without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// Run the gc subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &GcArgs) -> Result<()> {
+    let policy = resolve_policy(global, args)?;
+    if !policy.is_enabled() {
+        anyhow::bail!(
+            "No retention rules configured: set keep_last, keep_days, keep_weekly or \
+             keep_monthly under [retention] in config.toml, or pass --keep-*"
+        );
+    }
+
+    let (source, generations) = cli::load_generations(global, backend)?;
+    collect(backend, &source, &generations, &policy, args.yes, global.dry_run)
+}
+
+/// Delete the generations of a profile that the policy does not keep
+fn collect(
+    backend: &dyn NixBackend,
+    source: &GenerationSource,
+    generations: &[Generation],
+    policy: &RetentionPolicy,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let plan = policy::evaluate(policy, generations, chrono::Local::now());
+
+    print!("{}", format_plan(&plan, generations));
+
+    let ids = plan.to_delete();
+    if ids.is_empty() {
+        println!("Nothing to delete.");
+        return Ok(());
+    }
+
+    if !dry_run && !yes && !confirm(ids.len())? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let result = backend.delete_generations(
+        &source.profile_path,
+        &ids,
+        source.profile_type,
+        dry_run,
+    )?;
+
+    if dry_run {
+        println!("{}\n  {}", result.message, result.command);
+        Ok(())
+    } else if result.success {
+        println!("{}", result.message);
+        Ok(())
+    } else {
+        anyhow::bail!("{}", result.message)
+    }
+}
+
+/// Combine the configured (or `--policy`) rules with command-line overrides
+fn resolve_policy(global: &GlobalArgs, args: &GcArgs) -> Result<RetentionPolicy> {
+    let mut policy = match &args.policy {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read policy from {:?}", path))?;
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse policy from {:?}", path))?
+        }
+        None => global.config()?.retention,
+    };
+
+    if args.keep_last.is_some() {
+        policy.keep_last = args.keep_last;
+    }
+    if args.keep_days.is_some() {
+        policy.keep_days = args.keep_days;
+    }
+    if args.keep_weekly.is_some() {
+        policy.keep_weekly = args.keep_weekly;
+    }
+    if args.keep_monthly.is_some() {
+        policy.keep_monthly = args.keep_monthly;
+    }
+
+    Ok(policy)
+}
+
+/// Ask for confirmation on the terminal
+fn confirm(count: usize) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("Refusing to delete without confirmation; pass --yes to proceed");
+    }
+
+    print!("Delete {} generation(s)? [y/N] ", count);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Format the plan as a table of generations with their verdict
+fn format_plan(plan: &RetentionPlan, generations: &[Generation]) -> String {
+    let mut out = format!("{:<6} {:<15} {:>10}  {:<7} {}\n", "GEN", "DATE", "SIZE", "ACTION", "REASON");
+
+    for decision in &plan.decisions {
+        let Some(gen) = generations.iter().find(|g| g.id == decision.id) else {
+            continue;
+        };
+        let reasons: Vec<&str> = decision.reasons.iter().map(|r| r.label()).collect();
+        out.push_str(&format!(
+            "{:<6} {:<15} {:>10}  {:<7} {}\n",
+            gen.id,
+            gen.formatted_date(),
+            gen.formatted_size(),
+            if decision.is_kept() { "keep" } else { "delete" },
+            reasons.join(", "),
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use crate::nix::fake::FakeBackend;
+    use clap::Parser;
+
+    #[test]
+    fn test_overrides_replace_config_rules() {
+        let cli = Cli::try_parse_from(["nixhist", "gc", "--keep-last", "3", "--yes"]).unwrap();
+        let Some(Command::Gc(args)) = &cli.command else {
+            panic!("expected gc command");
+        };
+        assert!(args.yes);
+
+        let dir = std::env::temp_dir().join(format!("nixhist-gc-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let policy_file = dir.join("policy.toml");
+        std::fs::write(&policy_file, "keep_last = 10\nkeep_days = 30\n").unwrap();
+
+        let args = GcArgs { policy: Some(policy_file), ..args.clone() };
+        let policy = resolve_policy(&cli.global, &args).unwrap();
+        assert_eq!(policy.keep_last, Some(3));
+        assert_eq!(policy.keep_days, Some(30));
+        assert!(policy.keep_pinned);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_deletes_through_backend() {
+        let (info, backend) = FakeBackend::fixture();
+        let source = GenerationSource::system(&info);
+        let mut generations = backend.list_generations(&source).unwrap();
+        generations.iter_mut().find(|g| g.id == 140).unwrap().is_pinned = true;
+        let policy = RetentionPolicy { keep_last: Some(1), ..RetentionPolicy::default() };
+
+        // A dry run leaves the profile alone
+        collect(&backend, &source, &generations, &policy, false, true).unwrap();
+        assert_eq!(backend.list_generations(&source).unwrap().len(), 3);
+
+        // The newest and the pinned generation are kept
+        collect(&backend, &source, &generations, &policy, true, false).unwrap();
+        let kept: Vec<u32> = backend.list_generations(&source).unwrap().iter().map(|g| g.id).collect();
+        assert_eq!(kept, [142, 140]);
+
+        // Other profiles are untouched
+        let hm = GenerationSource::home_manager(&info).unwrap();
+        assert_eq!(backend.list_generations(&hm).unwrap().len(), 2);
+    }
+}
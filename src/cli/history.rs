@@ -0,0 +1,105 @@
+//! `nixhist history` - version history of a package across generations
+//!
+//! Usage: nixhist history <PACKAGE> [--profile system|home-manager] [--format table|json|csv]
+//!
+//! Lists every generation in which the package was added, changed
+//! version or was removed, oldest first.
+
+use crate::cli::{self, csv_field, GlobalArgs, OutputFormat};
+use crate::nix::{package_history, HistoryEntry, NixBackend, PackageCache};
+use anyhow::{Context, Result};
+use clap::Args;
+
+/// Arguments of the history subcommand
+#[derive(Debug, Clone, Args)]
+pub struct HistoryArgs {
+    /// Package name (as shown by `nixhist packages`)
+    pub package: String,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// Run the history subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &HistoryArgs) -> Result<()> {
+    let (source, generations) = cli::load_generations(global, backend)?;
+
+    let mut cache = PackageCache::new();
+    let history = package_history(backend, &args.package, &generations, &source, &mut cache, &mut |_, _| true)
+        .unwrap_or_default();
+    if history.is_empty() {
+        anyhow::bail!(
+            "{} is not in any {} generation",
+            args.package,
+            global.profile().name()
+        );
+    }
+
+    let output = match args.format {
+        OutputFormat::Table => format_table(&history),
+        OutputFormat::Json => serde_json::to_string_pretty(&history)
+            .context("Failed to serialize history")?,
+        OutputFormat::Csv => format_csv(&history),
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Format history as an aligned text table
+fn format_table(history: &[HistoryEntry]) -> String {
+    let mut lines = vec![format!("{:<6} {:<17} {:<11} {}", "GEN", "DATE", "CHANGE", "VERSION")];
+
+    for entry in history {
+        lines.push(format!(
+            "{:<6} {:<17} {:<11} {}",
+            entry.gen_id,
+            entry.date.format("%Y-%m-%d %H:%M"),
+            entry.change_label(),
+            entry.version_text(),
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Format history as CSV with a header row
+fn format_csv(history: &[HistoryEntry]) -> String {
+    let mut lines = vec!["id,date,change,previous_version,version".to_string()];
+    for entry in history {
+        lines.push(format!(
+            "{},{},{},{},{}",
+            entry.gen_id,
+            entry.date.to_rfc3339(),
+            entry.change_label(),
+            csv_field(entry.previous.as_deref().unwrap_or("")),
+            csv_field(entry.version.as_deref().unwrap_or("")),
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_format_table_and_csv() {
+        let date = Local.with_ymd_and_hms(2024, 2, 1, 9, 30, 0).unwrap();
+        let history = vec![
+            HistoryEntry { gen_id: 120, date, version: Some("121.0".into()), previous: None },
+            HistoryEntry { gen_id: 131, date, version: Some("122.0".into()), previous: Some("121.0".into()) },
+            HistoryEntry { gen_id: 140, date, version: None, previous: Some("122.0".into()) },
+        ];
+
+        let table = format_table(&history);
+        assert!(table.contains("131    2024-02-01 09:30  upgraded    121.0 → 122.0"));
+        assert!(table.contains("140    2024-02-01 09:30  removed     122.0"));
+
+        let csv = format_csv(&history);
+        assert!(csv.starts_with("id,date,change,previous_version,version\n120,"));
+        assert!(csv.ends_with(",removed,122.0,"));
+    }
+}
@@ -0,0 +1,136 @@
+//! `nixhist list` - print generations of a profile
+//!
+//! Usage: nixhist list [--profile system|home-manager] [--format table|json|csv]
+
+use crate::cli::{self, csv_field, GlobalArgs, OutputFormat};
+use crate::nix::NixBackend;
+use crate::types::Generation;
+use anyhow::{Context, Result};
+use clap::Args;
+
+/// Arguments of the list subcommand
+#[derive(Debug, Clone, Args)]
+pub struct ListArgs {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// Run the list subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &ListArgs) -> Result<()> {
+    let (_, generations) = cli::load_generations(global, backend)?;
+
+    let output = match args.format {
+        OutputFormat::Table => format_table(&generations),
+        OutputFormat::Json => serde_json::to_string_pretty(&generations)
+            .context("Failed to serialize generations")?,
+        OutputFormat::Csv => format_csv(&generations),
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Status flags of a generation (same as the Manage tab)
+fn status(gen: &Generation) -> String {
+    let mut flags = Vec::new();
+    if gen.is_current {
+        flags.push("current");
+    }
+    if gen.is_pinned {
+        flags.push("pinned");
+    }
+    if gen.in_bootloader {
+        flags.push("boot");
+    }
+    flags.join(",")
+}
+
+/// Format generations as an aligned text table
+fn format_table(generations: &[Generation]) -> String {
+    let mut lines = vec![format!(
+        "{:<6} {:<15} {:<28} {:<12} {:>6} {:>10}  {}",
+        "GEN", "DATE", "VERSION", "KERNEL", "PKGS", "SIZE", "STATUS"
+    )];
+
+    for gen in generations {
+        lines.push(format!(
+            "{:<6} {:<15} {:<28} {:<12} {:>6} {:>10}  {}",
+            gen.id,
+            gen.formatted_date(),
+            gen.nixos_version.as_deref().unwrap_or("-"),
+            gen.kernel_version.as_deref().unwrap_or("-"),
+            gen.package_count,
+            gen.formatted_size(),
+            status(gen),
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Format generations as CSV with a header row
+fn format_csv(generations: &[Generation]) -> String {
+    let mut lines = vec![
+        "id,date,is_current,nixos_version,kernel_version,package_count,closure_size,store_path,is_pinned,in_bootloader"
+            .to_string(),
+    ];
+
+    for gen in generations {
+        lines.push(format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            gen.id,
+            gen.date.to_rfc3339(),
+            gen.is_current,
+            csv_field(gen.nixos_version.as_deref().unwrap_or("")),
+            csv_field(gen.kernel_version.as_deref().unwrap_or("")),
+            gen.package_count,
+            gen.closure_size,
+            csv_field(&gen.store_path),
+            gen.is_pinned,
+            gen.in_bootloader,
+        ));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn sample_generation() -> Generation {
+        Generation {
+            id: 142,
+            date: Local::now(),
+            is_current: true,
+            nixos_version: Some("24.11.20241201.abcdef".into()),
+            kernel_version: Some("6.6.52".into()),
+            package_count: 812,
+            closure_size: 1_500_000_000,
+            closure_count: None,
+            store_path: "/nix/store/abc-nixos-system-host-24.11".into(),
+            is_pinned: true,
+            in_bootloader: true,
+            specialisations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let csv = format_csv(&[sample_generation()]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id,date,"));
+        assert!(lines[1].starts_with("142,"));
+        assert!(lines[1].ends_with(",true,true"));
+    }
+
+    #[test]
+    fn test_format_table() {
+        let table = format_table(&[sample_generation()]);
+        assert!(table.contains("142"));
+        assert!(table.contains("current,pinned,boot"));
+    }
+}
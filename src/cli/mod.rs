@@ -0,0 +1,409 @@
+//! Command-line interface
+//!
+//! Defines the argument parser (global options and subcommands).
+//! Subcommands print generation data and exit without
+//! touching the terminal, for use in scripts, status bars and CI:
+//! - `list`: print the generations of a profile
+//! - `diff`: compare the packages of two generations
+//! - `packages`: list the packages of a generation
+//! - `gc`: delete generations according to the retention policy
+//! - `pin`, `unpin`, `pins`: manage pinned generations
+//! - `why`: show why a package is in a generation
+//! - `history`: version history of a package across generations
+//! - `report`: export a Markdown/HTML generation report
+//! - `cache`: clear the metadata cache
+//! - `completions`, `manpage`: shell completions and man page
+
+pub mod cache;
+pub mod completions;
+pub mod diff;
+pub mod gc;
+pub mod history;
+pub mod list;
+pub mod packages;
+pub mod pin;
+pub mod report;
+pub mod why;
+
+use crate::config::{Config, ThemeName};
+use crate::nix::{self, GenerationSource, NixBackend, SystemInfo};
+use crate::types::{Generation, ProfileId, ProfileType, Tab};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+/// Keybinding reference appended to `--help`
+const AFTER_HELP: &str = "\
+KEYBINDINGS:
+    1-5              Switch tabs
+    j/k              Navigate up/down
+    Tab              Switch panel/focus
+    Enter            Select/confirm
+    Space            Toggle selection (Manage tab)
+    R                Restore generation
+    D                Delete generation(s)
+    P                Pin/unpin generation
+    /                Filter (Packages tab)
+    q                Quit
+
+TABS:
+    [1] Overview     View all generations
+    [2] Packages     Browse packages in a generation
+    [3] Diff         Compare two generations
+    [4] Manage       Restore, delete, pin generations
+    [5] Settings     Configure theme and display options
+
+CONFIG:
+    ~/.config/nixhist/config.toml";
+
+/// nixhist - NixOS Generation Dashboard
+#[derive(Debug, Parser)]
+#[command(
+    name = "nixhist",
+    version,
+    about = "A TUI for viewing, comparing, and managing NixOS generations",
+    after_help = AFTER_HELP,
+    disable_version_flag = true
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// Run a headless command instead of the TUI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Options shared by the TUI and all subcommands
+#[derive(Debug, Clone, Args)]
+pub struct GlobalArgs {
+    /// Show what would be done without executing
+    #[arg(short = 'n', long, global = true)]
+    pub dry_run: bool,
+
+    /// Use this config file instead of ~/.config/nixhist/config.toml
+    #[arg(long, value_name = "PATH", value_parser = existing_file, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Override the configured theme for this run
+    #[arg(long, global = true)]
+    pub theme: Option<ThemeName>,
+
+    /// Profile to operate on: system, home-manager, nix-profile or a
+    /// profile name from config.toml (default: system)
+    #[arg(short, long, value_name = "PROFILE", value_parser = ProfileId::parse, global = true)]
+    pub profile: Option<ProfileId>,
+
+    /// Path of the system profile symlink
+    #[arg(long, value_name = "PATH", global = true)]
+    pub system_profile: Option<PathBuf>,
+
+    /// Ignore Home-Manager even if it is installed
+    #[arg(long, global = true)]
+    pub no_home_manager: bool,
+
+    /// Path of the `nix profile` user profile symlink
+    #[arg(long, value_name = "PATH", global = true)]
+    pub nix_profile: Option<PathBuf>,
+
+    /// Read all metadata from the store, ignoring (and not updating) the cache
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Tab to open when the TUI starts
+    #[arg(long, value_name = "TAB")]
+    pub start_tab: Option<Tab>,
+
+    /// Print version information
+    #[arg(short = 'v', long, action = clap::ArgAction::Version)]
+    pub version: Option<bool>,
+}
+
+/// Headless subcommands
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print generations and exit
+    List(list::ListArgs),
+    /// Print package changes between two generations
+    #[command(after_help = diff::EXIT_CODES_HELP)]
+    Diff(diff::DiffArgs),
+    /// Print the packages of a generation
+    Packages(packages::PackagesArgs),
+    /// Delete generations according to the retention policy
+    Gc(gc::GcArgs),
+    /// Pin a generation
+    Pin(pin::PinArgs),
+    /// Unpin a generation
+    Unpin(pin::UnpinArgs),
+    /// Print pinned generations
+    Pins(pin::PinsArgs),
+    /// Show why a package is in a generation
+    Why(why::WhyArgs),
+    /// Print the version history of a package across generations
+    History(history::HistoryArgs),
+    /// Export a Markdown or HTML report of generations and changes
+    Report(report::ReportArgs),
+    /// Manage the metadata cache
+    Cache(cache::CacheArgs),
+    /// Print a shell completion script
+    Completions(completions::CompletionsArgs),
+    /// Print the man page (roff)
+    Manpage,
+}
+
+impl GlobalArgs {
+    /// Profile selected with `--profile`, defaulting to System
+    pub fn profile(&self) -> ProfileId {
+        self.profile.clone().unwrap_or(ProfileId::SYSTEM)
+    }
+
+    /// Detect the system and apply `--system-profile` / `--no-home-manager` / `--nix-profile`
+    pub fn system_info(&self) -> Result<SystemInfo> {
+        let mut system_info = nix::detect_system()
+            .context("Failed to detect system configuration")?;
+
+        if let Some(path) = &self.system_profile {
+            system_info.system_profile = path.clone();
+        }
+        if self.no_home_manager {
+            system_info.home_manager = None;
+        }
+        if let Some(path) = &self.nix_profile {
+            system_info.nix_profile = Some(path.clone());
+        }
+
+        Ok(system_info)
+    }
+
+    /// Load the config and apply `--theme`
+    pub fn config(&self) -> Result<Config> {
+        let mut config = Config::load()
+            .context("Failed to load configuration")?;
+
+        if let Some(theme) = self.theme {
+            config.theme = theme;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Value parser for paths that must point to an existing file
+fn existing_file(s: &str) -> std::result::Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("no such file: {}", s))
+    }
+}
+
+/// Output format for headless commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Resolve the generation source for a profile
+pub fn resolve_source(
+    system_info: &SystemInfo,
+    config: &Config,
+    profile: &ProfileId,
+) -> Result<GenerationSource> {
+    match profile {
+        ProfileId::Builtin(ProfileType::System) => Ok(GenerationSource::system(system_info)),
+        ProfileId::Builtin(ProfileType::HomeManager) => GenerationSource::home_manager(system_info)
+            .context("Home-Manager was not detected on this system"),
+        ProfileId::Builtin(ProfileType::NixProfile) => GenerationSource::nix_profile(system_info)
+            .context("No `nix profile` user profile was detected on this system"),
+        ProfileId::Builtin(ProfileType::NixEnv) => {
+            anyhow::bail!("nix-env profiles must be declared under [[profiles]] in config.toml")
+        }
+        ProfileId::Custom(name) => config
+            .custom_profile(name)
+            .map(GenerationSource::custom)
+            .with_context(|| format!("No profile named '{}' in config.toml", name)),
+    }
+}
+
+/// Load the generations of the selected profile with pin status applied
+pub fn load_generations(
+    global: &GlobalArgs,
+    backend: &dyn NixBackend,
+) -> Result<(GenerationSource, Vec<Generation>)> {
+    let system_info = global.system_info()?;
+    let config = global.config()?;
+    let profile = global.profile();
+
+    let source = resolve_source(&system_info, &config, &profile)?;
+    let mut generations = backend.list_generations(&source)
+        .with_context(|| format!("Failed to list {} generations", profile.name()))?;
+
+    for gen in &mut generations {
+        gen.is_pinned = config.is_pinned(&profile, gen.id);
+    }
+
+    Ok((source, generations))
+}
+
+/// A generation as named on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationSpec {
+    /// Explicit generation ID
+    Id(u32),
+    /// The currently active generation
+    Current,
+    /// The generation before the current one
+    Previous,
+    /// The generation the system was booted into
+    Booted,
+}
+
+impl GenerationSpec {
+    /// Parse a generation ID or one of the aliases `current`, `previous`, `booted`
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "current" => Ok(GenerationSpec::Current),
+            "previous" => Ok(GenerationSpec::Previous),
+            "booted" => Ok(GenerationSpec::Booted),
+            _ => s.parse()
+                .map(GenerationSpec::Id)
+                .map_err(|_| anyhow::anyhow!(
+                    "Invalid generation '{}' (expected an ID, current, previous or booted)",
+                    s
+                )),
+        }
+    }
+
+    /// Find the generation this spec refers to
+    pub fn resolve<'a>(&self, generations: &'a [Generation]) -> Result<&'a Generation> {
+        let current = || {
+            generations.iter()
+                .find(|g| g.is_current)
+                .context("No current generation found")
+        };
+
+        match self {
+            GenerationSpec::Id(id) => generations.iter()
+                .find(|g| g.id == *id)
+                .with_context(|| format!("Generation {} not found", id)),
+            GenerationSpec::Current => current(),
+            GenerationSpec::Previous => {
+                let current_id = current()?.id;
+                generations.iter()
+                    .filter(|g| g.id < current_id)
+                    .max_by_key(|g| g.id)
+                    .context("No generation before the current one")
+            }
+            GenerationSpec::Booted => {
+                let booted = std::fs::read_link(Path::new("/run/booted-system"))
+                    .context("Failed to read /run/booted-system")?;
+                let booted = booted.to_string_lossy();
+                generations.iter()
+                    .find(|g| g.store_path == booted)
+                    .context("The booted system does not match any generation")
+            }
+        }
+    }
+}
+
+/// Escape a single CSV field (RFC 4180)
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_global_options() {
+        let cli = Cli::try_parse_from([
+            "nixhist", "--theme", "nord", "--no-home-manager", "--start-tab", "diff",
+        ])
+        .unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.global.theme, Some(ThemeName::Nord));
+        assert_eq!(cli.global.start_tab, Some(Tab::Diff));
+        assert!(cli.global.no_home_manager);
+
+        let cli = Cli::try_parse_from(["nixhist", "list", "--profile", "home-manager", "-f", "csv"])
+            .unwrap();
+        assert_eq!(cli.global.profile(), ProfileId::Builtin(ProfileType::HomeManager));
+        match cli.command {
+            Some(Command::List(args)) => assert_eq!(args.format, OutputFormat::Csv),
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let cli = Cli::try_parse_from(["nixhist", "list", "-p", "devtools"]).unwrap();
+        assert_eq!(cli.global.profile(), ProfileId::Custom("devtools".into()));
+
+        let cli = Cli::try_parse_from(["nixhist", "cache", "clear", "--no-cache"]).unwrap();
+        assert!(cli.global.no_cache);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Cache(cache::CacheArgs { action: cache::CacheAction::Clear }))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_arguments() {
+        assert!(Cli::try_parse_from(["nixhist", "--bogus"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "--theme", "solarized"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "--config"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "--config", "/nonexistent/config.toml"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "list", "--format", "xml"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "diff"]).is_err());
+        assert!(Cli::try_parse_from(["nixhist", "diff", "140", "latest"]).is_err());
+    }
+
+    #[test]
+    fn test_generation_spec() {
+        assert_eq!(GenerationSpec::parse("140").unwrap(), GenerationSpec::Id(140));
+        assert_eq!(GenerationSpec::parse("previous").unwrap(), GenerationSpec::Previous);
+        assert!(GenerationSpec::parse("latest").is_err());
+
+        let gens: Vec<Generation> = [(142, false), (141, true), (139, false)]
+            .iter()
+            .map(|&(id, is_current)| Generation {
+                id,
+                date: chrono::Local::now(),
+                is_current,
+                nixos_version: None,
+                kernel_version: None,
+                package_count: 0,
+                closure_size: 0,
+                closure_count: None,
+                store_path: String::new(),
+                is_pinned: false,
+                in_bootloader: false,
+                specialisations: Vec::new(),
+            })
+            .collect();
+
+        assert_eq!(GenerationSpec::Current.resolve(&gens).unwrap().id, 141);
+        assert_eq!(GenerationSpec::Previous.resolve(&gens).unwrap().id, 139);
+        assert_eq!(GenerationSpec::Id(142).resolve(&gens).unwrap().id, 142);
+        assert!(GenerationSpec::Id(1).resolve(&gens).is_err());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
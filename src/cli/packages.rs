@@ -0,0 +1,217 @@
+//! `nixhist packages` - list the packages of a generation
+//!
+//! Usage: nixhist packages <GENERATION> [--filter PATTERN] [--sort name|size|version]
+//!                         [--top N] [--format table|json|csv]
+
+use crate::cli::completions::generation_candidates;
+use crate::cli::{self, csv_field, GenerationSpec, GlobalArgs, OutputFormat};
+use crate::nix::{compare_versions, NixBackend};
+use crate::types::Package;
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use clap_complete::ArgValueCandidates;
+use regex::Regex;
+
+/// Arguments of the packages subcommand
+#[derive(Debug, Clone, Args)]
+pub struct PackagesArgs {
+    /// Generation: an ID, current, previous or booted
+    #[arg(value_parser = GenerationSpec::parse, add = ArgValueCandidates::new(generation_candidates))]
+    pub generation: GenerationSpec,
+
+    /// Only show packages whose name matches (substring or regex, case-insensitive)
+    #[arg(long, value_name = "PATTERN")]
+    pub filter: Option<String>,
+
+    /// Sort order (default: name, or size with --top)
+    #[arg(long, value_enum)]
+    pub sort: Option<SortKey>,
+
+    /// Only show the N largest packages
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// Sort order for package lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    Name,
+    Size,
+    Version,
+}
+
+/// Run the packages subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &PackagesArgs) -> Result<()> {
+    let (source, generations) = cli::load_generations(global, backend)?;
+    let gen = args.generation.resolve(&generations)?;
+
+    let packages = backend
+        .get_packages(&source.generation_path(gen.id))
+        .with_context(|| format!("Failed to read packages of generation {}", gen.id))?;
+
+    let filter = args.filter.as_deref().map(build_filter).transpose()?;
+    let packages = select(packages, filter.as_ref(), args.sort, args.top);
+
+    let output = match args.format {
+        OutputFormat::Table => format_table(&packages),
+        OutputFormat::Json => serde_json::to_string_pretty(&packages)
+            .context("Failed to serialize packages")?,
+        OutputFormat::Csv => format_csv(&packages),
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Build a case-insensitive name filter
+///
+/// The pattern is used as a regex if it is one, otherwise as a plain substring.
+fn build_filter(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("(?i){}", pattern))
+        .or_else(|_| Regex::new(&format!("(?i){}", regex::escape(pattern))))
+        .with_context(|| format!("Invalid filter: {}", pattern))
+}
+
+/// Apply filter, `--top` and sort order to a package list
+fn select(
+    mut packages: Vec<Package>,
+    filter: Option<&Regex>,
+    sort: Option<SortKey>,
+    top: Option<usize>,
+) -> Vec<Package> {
+    if let Some(re) = filter {
+        packages.retain(|p| re.is_match(&p.name));
+    }
+
+    if let Some(n) = top {
+        packages.sort_by_key(|p| std::cmp::Reverse(p.size));
+        packages.truncate(n);
+    }
+
+    let default_sort = if top.is_some() { SortKey::Size } else { SortKey::Name };
+    match sort.unwrap_or(default_sort) {
+        SortKey::Name => packages.sort_by_key(|p| p.name.to_lowercase()),
+        SortKey::Size => packages.sort_by_key(|p| std::cmp::Reverse(p.size)),
+        SortKey::Version => packages.sort_by(|a, b| {
+            compare_versions(&a.version, &b.version)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        }),
+    }
+
+    packages
+}
+
+/// Format packages as an aligned text table
+///
+/// Multi-output packages get a column with the size of each output,
+/// `nix profile` elements one with their flake attribute.
+fn format_table(packages: &[Package]) -> String {
+    let name_width = packages.iter().map(|p| p.name.len()).max().unwrap_or(0).max(4);
+    let version_width = packages.iter().map(|p| p.version.len()).max().unwrap_or(0).max(7);
+    let show_attr = packages.iter().any(|p| p.attr_path.is_some());
+    let outputs: Vec<String> = packages.iter().map(Package::formatted_outputs).collect();
+    let outputs_width = outputs.iter().map(String::len).max().unwrap_or(0);
+
+    let mut header = format!(
+        "{:<nw$}  {:<vw$}  {:>10}",
+        "NAME", "VERSION", "SIZE",
+        nw = name_width,
+        vw = version_width,
+    );
+    if outputs_width > 0 {
+        header.push_str(&format!("  {:<ow$}", "OUTPUTS", ow = outputs_width.max(7)));
+    }
+    if show_attr {
+        header.push_str("  ATTRIBUTE");
+    }
+    let mut lines = vec![header];
+
+    for (pkg, outputs) in packages.iter().zip(&outputs) {
+        let mut line = format!(
+            "{:<nw$}  {:<vw$}  {:>10}",
+            pkg.name,
+            pkg.version,
+            pkg.formatted_size(),
+            nw = name_width,
+            vw = version_width,
+        );
+        if outputs_width > 0 {
+            line.push_str(&format!("  {:<ow$}", outputs, ow = outputs_width.max(7)));
+        }
+        if let Some(attr) = pkg.attr_path.as_deref().filter(|_| show_attr) {
+            line.push_str("  ");
+            line.push_str(attr);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Format packages as CSV with a header row
+fn format_csv(packages: &[Package]) -> String {
+    let mut lines = vec!["name,version,size".to_string()];
+    for pkg in packages {
+        lines.push(format!("{},{},{}", csv_field(&pkg.name), csv_field(&pkg.version), pkg.size));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pkg(name: &str, version: &str, size: u64) -> Package {
+        Package { name: name.into(), version: version.into(), size, attr_path: None, outputs: Vec::new() }
+    }
+
+    fn sample() -> Vec<Package> {
+        vec![
+            pkg("firefox", "122.0", 300),
+            pkg("python3", "3.11.9", 200),
+            pkg("python3", "3.9.19", 150),
+            pkg("bash", "5.2", 10),
+        ]
+    }
+
+    #[test]
+    fn test_filter_substring_and_regex() {
+        let re = build_filter("PYTHON").unwrap();
+        assert_eq!(select(sample(), Some(&re), None, None).len(), 2);
+
+        let re = build_filter("^(bash|firefox)$").unwrap();
+        assert_eq!(select(sample(), Some(&re), None, None).len(), 2);
+
+        // Not a valid regex: matched literally
+        let re = build_filter("c++(").unwrap();
+        assert!(select(sample(), Some(&re), None, None).is_empty());
+    }
+
+    #[test]
+    fn test_top_by_size() {
+        let top = select(sample(), None, None, Some(2));
+        let names: Vec<&str> = top.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["firefox", "python3"]);
+
+        let top = select(sample(), None, Some(SortKey::Name), Some(3));
+        let names: Vec<&str> = top.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["firefox", "python3", "python3"]);
+    }
+
+    #[test]
+    fn test_sort_version() {
+        let sorted = select(sample(), None, Some(SortKey::Version), None);
+        let versions: Vec<&str> = sorted.iter().map(|p| p.version.as_str()).collect();
+        assert_eq!(versions, ["3.9.19", "3.11.9", "5.2", "122.0"]);
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let csv = format_csv(&[pkg("foo", "1.0", 42)]);
+        assert_eq!(csv, "name,version,size\nfoo,1.0,42");
+    }
+}
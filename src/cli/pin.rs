@@ -0,0 +1,215 @@
+//! `nixhist pin`, `nixhist unpin`, `nixhist pins` - manage pinned generations
+//!
+//! Usage: nixhist pin <GENERATION> [--note TEXT]
+//!        nixhist unpin <GENERATION>
+//!        nixhist pins [--format table|json|csv]
+//!
+//! Pins are stored in config.toml, the same as pinning with `P` in the
+//! Manage tab. All three commands honour `--profile`; `pins` lists every
+//! configured profile (including the named ones in config.toml) unless
+//! `--profile` narrows it to one.
+
+use crate::cli::completions::generation_candidates;
+use crate::cli::{self, csv_field, GenerationSpec, GlobalArgs, OutputFormat};
+use crate::config::Config;
+use crate::nix::NixBackend;
+use crate::types::{Generation, ProfileId, ProfileType};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use clap::Args;
+use clap_complete::ArgValueCandidates;
+use serde::Serialize;
+
+/// Arguments of the pin subcommand
+#[derive(Debug, Clone, Args)]
+pub struct PinArgs {
+    /// Generation: an ID, current, previous or booted
+    #[arg(value_parser = GenerationSpec::parse, add = ArgValueCandidates::new(generation_candidates))]
+    pub generation: GenerationSpec,
+
+    /// Why the generation is pinned (replaces an existing note)
+    #[arg(long, value_name = "TEXT")]
+    pub note: Option<String>,
+}
+
+/// Arguments of the unpin subcommand
+#[derive(Debug, Clone, Args)]
+pub struct UnpinArgs {
+    /// Generation: an ID, current, previous or booted
+    #[arg(value_parser = GenerationSpec::parse, add = ArgValueCandidates::new(generation_candidates))]
+    pub generation: GenerationSpec,
+}
+
+/// Arguments of the pins subcommand
+#[derive(Debug, Clone, Args)]
+pub struct PinsArgs {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// A pinned generation as printed by `nixhist pins`
+#[derive(Debug, Clone, Serialize)]
+struct PinEntry {
+    profile: ProfileId,
+    id: u32,
+    /// None if the generation no longer exists
+    date: Option<DateTime<Local>>,
+    note: Option<String>,
+}
+
+/// Run the pin subcommand
+pub fn run_pin(global: &GlobalArgs, backend: &dyn NixBackend, args: &PinArgs) -> Result<()> {
+    let profile = global.profile();
+    let (_, generations) = cli::load_generations(global, backend)?;
+    let gen = args.generation.resolve(&generations)?;
+
+    let mut config = Config::load().context("Failed to load configuration")?;
+    config.pin(&profile, gen.id, args.note.clone());
+    config.save()?;
+
+    println!("Pinned {} generation {}", profile.name(), gen.id);
+    Ok(())
+}
+
+/// Run the unpin subcommand
+pub fn run_unpin(global: &GlobalArgs, backend: &dyn NixBackend, args: &UnpinArgs) -> Result<()> {
+    let profile = global.profile();
+
+    // Explicit IDs need not exist any more, so stale pins can be removed
+    let id = match args.generation {
+        GenerationSpec::Id(id) => id,
+        spec => {
+            let (_, generations) = cli::load_generations(global, backend)?;
+            spec.resolve(&generations)?.id
+        }
+    };
+
+    let mut config = Config::load().context("Failed to load configuration")?;
+    if !config.unpin(&profile, id) {
+        anyhow::bail!("{} generation {} is not pinned", profile.name(), id);
+    }
+    config.save()?;
+
+    println!("Unpinned {} generation {}", profile.name(), id);
+    Ok(())
+}
+
+/// Run the pins subcommand
+pub fn run_pins(global: &GlobalArgs, backend: &dyn NixBackend, args: &PinsArgs) -> Result<()> {
+    let config = global.config()?;
+    let system_info = global.system_info()?;
+
+    let profiles = match &global.profile {
+        Some(profile) => vec![profile.clone()],
+        None => [ProfileType::System, ProfileType::HomeManager, ProfileType::NixProfile]
+            .into_iter()
+            .map(ProfileId::from)
+            .chain(config.profiles.iter().map(|p| ProfileId::Custom(p.name.clone())))
+            .collect(),
+    };
+
+    let mut entries = Vec::new();
+    for profile in profiles {
+        let ids = config.pinned_ids(&profile);
+        if ids.is_empty() {
+            continue;
+        }
+
+        // Dates are informational; a missing profile just leaves them empty
+        let generations = cli::resolve_source(&system_info, &config, &profile)
+            .and_then(|source| backend.list_generations(&source))
+            .unwrap_or_default();
+
+        entries.extend(collect_entries(&config, &profile, &ids, &generations));
+    }
+
+    let output = match args.format {
+        OutputFormat::Table => format_table(&entries),
+        OutputFormat::Json => serde_json::to_string_pretty(&entries)
+            .context("Failed to serialize pins")?,
+        OutputFormat::Csv => format_csv(&entries),
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Combine pinned IDs with their notes and generation dates
+fn collect_entries(
+    config: &Config,
+    profile: &ProfileId,
+    ids: &[u32],
+    generations: &[Generation],
+) -> Vec<PinEntry> {
+    ids.iter()
+        .map(|&id| PinEntry {
+            profile: profile.clone(),
+            id,
+            date: generations.iter().find(|g| g.id == id).map(|g| g.date),
+            note: config.pin_note(profile, id).map(str::to_string),
+        })
+        .collect()
+}
+
+/// Format pins as an aligned text table
+fn format_table(entries: &[PinEntry]) -> String {
+    if entries.is_empty() {
+        return "No pinned generations".to_string();
+    }
+
+    let mut lines = vec![format!("{:<14} {:<6} {:<17} {}", "PROFILE", "GEN", "DATE", "NOTE")];
+    for entry in entries {
+        lines.push(format!(
+            "{:<14} {:<6} {:<17} {}",
+            entry.profile.name(),
+            entry.id,
+            entry.date
+                .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "(deleted)".to_string()),
+            entry.note.as_deref().unwrap_or(""),
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Format pins as CSV with a header row
+fn format_csv(entries: &[PinEntry]) -> String {
+    let mut lines = vec!["profile,id,date,note".to_string()];
+    for entry in entries {
+        lines.push(format!(
+            "{},{},{},{}",
+            entry.profile.name(),
+            entry.id,
+            entry.date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            csv_field(entry.note.as_deref().unwrap_or("")),
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_and_format() {
+        let mut config = Config::default();
+        config.pin(&ProfileId::SYSTEM, 140, Some("known good, nvidia".into()));
+        config.pin(&ProfileId::SYSTEM, 12, None);
+
+        let entries = collect_entries(&config, &ProfileId::SYSTEM, &[12, 140], &[]);
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.date.is_none()));
+
+        let table = format_table(&entries);
+        assert!(table.contains("(deleted)"));
+        assert!(table.contains("known good, nvidia"));
+
+        let csv = format_csv(&entries);
+        assert_eq!(
+            csv,
+            "profile,id,date,note\nSystem,12,,\nSystem,140,,\"known good, nvidia\""
+        );
+    }
+}
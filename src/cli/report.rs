@@ -0,0 +1,72 @@
+//! `nixhist report` - export a Markdown or HTML generation report
+//!
+//! Usage: nixhist report [--from GEN --to GEN] [--format markdown|html] [--output FILE]
+//!
+//! The report contains the generation list, pinned generations and,
+//! with `--from`/`--to`, the diff between two generations (or store paths).
+
+use crate::cli::{self, diff, diff::DiffTarget, GlobalArgs};
+use crate::nix::NixBackend;
+use crate::report::{self, Report, ReportDiff, ReportFormat};
+use anyhow::{Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+/// Arguments of the report subcommand
+#[derive(Debug, Clone, Args)]
+pub struct ReportArgs {
+    /// Old generation of the included diff: an ID, current, previous, booted or a store path
+    #[arg(long, value_parser = DiffTarget::parse, requires = "to")]
+    pub from: Option<DiffTarget>,
+
+    /// New generation (or store path) of the included diff
+    #[arg(long, value_parser = DiffTarget::parse, requires = "from")]
+    pub to: Option<DiffTarget>,
+
+    /// Report format
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Markdown)]
+    pub format: ReportFormat,
+
+    /// Write the report to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+/// Run the report subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &ReportArgs) -> Result<()> {
+    let system_info = global.system_info()?;
+    let config = global.config()?;
+    let profile = global.profile();
+    let (source, generations) = cli::load_generations(global, backend)?;
+
+    let diff = match (&args.from, &args.to) {
+        (Some(from), Some(to)) => {
+            let from = from.resolve(backend, &generations)?;
+            let to = to.resolve(backend, &generations)?;
+            let diff = diff::load_diff(backend, &source, &from, &to)?;
+            Some((from, to, diff))
+        }
+        _ => None,
+    };
+
+    let report = Report {
+        hostname: &system_info.hostname,
+        pins: report::pins(&config, &profile),
+        profile,
+        generations: &generations,
+        diff: diff.as_ref().map(|(from, to, diff)| ReportDiff { from: from.clone(), to: to.clone(), diff }),
+        created: chrono::Local::now(),
+    };
+    let document = report.render(args.format);
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, document)
+                .with_context(|| format!("Failed to write report to {:?}", path))?;
+            eprintln!("Report written to {}", path.display());
+        }
+        None => print!("{}", document),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! `nixhist why` - show why a package is in a generation
+//!
+//! Usage: nixhist why <PACKAGE> [GENERATION] [--json]
+//!
+//! Prints the shortest dependency chains from the generation to the
+//! package, like `nix why-depends --all`.
+
+use crate::cli::{self, GenerationSpec, GlobalArgs};
+use crate::nix::{NixBackend, WhyNode};
+use anyhow::{Context, Result};
+use clap::Args;
+
+/// Arguments of the why subcommand
+#[derive(Debug, Clone, Args)]
+pub struct WhyArgs {
+    /// Package name (as shown by `nixhist packages`)
+    pub package: String,
+
+    /// Generation: an ID, current, previous or booted
+    #[arg(value_parser = GenerationSpec::parse, default_value = "current")]
+    pub generation: GenerationSpec,
+
+    /// Print the tree as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Run the why subcommand
+pub fn run(global: &GlobalArgs, backend: &dyn NixBackend, args: &WhyArgs) -> Result<()> {
+    let (source, generations) = cli::load_generations(global, backend)?;
+    let gen = args.generation.resolve(&generations)?;
+
+    let tree = backend
+        .why_depends(&source.generation_path(gen.id), &[args.package.as_str()])
+        .with_context(|| format!("Failed to read the closure of generation {}", gen.id))?
+        .with_context(|| format!("{} is not in generation {}", args.package, gen.id))?;
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&tree).context("Failed to serialize tree")?
+        );
+    } else {
+        print!("{}", format_tree(&tree));
+    }
+
+    Ok(())
+}
+
+/// Format a tree with box-drawing branches
+fn format_tree(root: &WhyNode) -> String {
+    let mut out = format!("{}\n", root.path);
+    format_children(root, "", &mut out);
+    out
+}
+
+fn format_children(node: &WhyNode, prefix: &str, out: &mut String) {
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i == node.children.len() - 1;
+        out.push_str(prefix);
+        out.push_str(if last { "└───" } else { "├───" });
+        out.push_str(&child.name);
+        if child.repeated {
+            out.push_str(" …");
+        }
+        out.push('\n');
+
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        format_children(child, &child_prefix, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, children: Vec<WhyNode>) -> WhyNode {
+        WhyNode {
+            path: format!("/nix/store/xxx-{}", name),
+            name: name.into(),
+            is_target: children.is_empty(),
+            repeated: false,
+            children,
+        }
+    }
+
+    #[test]
+    fn test_format_tree() {
+        let tree = node("nixos-system", vec![
+            node("system-path", vec![node("firefox-122.0", vec![]), node("python3-3.11.9", vec![])]),
+            node("etc", vec![]),
+        ]);
+
+        assert_eq!(
+            format_tree(&tree),
+            "/nix/store/xxx-nixos-system\n\
+             ├───system-path\n\
+             │   ├───firefox-122.0\n\
+             │   └───python3-3.11.9\n\
+             └───etc\n"
+        );
+    }
+}
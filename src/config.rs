@@ -3,11 +3,16 @@
 //! Handles loading, saving, and default configuration values.
 //! Config file location: ~/.config/nixhist/config.toml
 
+use crate::types::{ProfileId, ProfileType};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Config file given on the command line (`--config`), if any
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
 
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +22,10 @@ pub struct Config {
     pub layout: LayoutMode,
     pub display: DisplayOptions,
     pub pinned: PinnedGenerations,
+    pub retention: RetentionPolicy,
+    /// Extra profiles (`[[profiles]]`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<CustomProfile>,
 }
 
 impl Default for Config {
@@ -26,13 +35,25 @@ impl Default for Config {
             layout: LayoutMode::Auto,
             display: DisplayOptions::default(),
             pinned: PinnedGenerations::default(),
+            retention: RetentionPolicy::default(),
+            profiles: Vec::new(),
         }
     }
 }
 
 impl Config {
+    /// Use a different config file for the rest of the process
+    pub fn set_path_override(path: PathBuf) {
+        // Only the first override counts; it is set once at startup
+        let _ = PATH_OVERRIDE.set(path);
+    }
+
     /// Get the config file path
     pub fn path() -> Result<PathBuf> {
+        if let Some(path) = PATH_OVERRIDE.get() {
+            return Ok(path.clone());
+        }
+
         let config_dir = dirs::config_dir()
             .context("Could not determine config directory")?
             .join("nixhist");
@@ -52,8 +73,30 @@ impl Config {
         let content = fs::read_to_string(&path)
             .with_context(|| format!("Failed to read config from {:?}", path))?;
 
-        toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config from {:?}", path))
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config from {:?}", path))?;
+        config.check_profiles()
+            .with_context(|| format!("Invalid [[profiles]] in {:?}", path))?;
+        Ok(config)
+    }
+
+    /// Reject `[[profiles]]` that `--profile` could not select: names of
+    /// built-in profiles (matched case-insensitively) and duplicates
+    fn check_profiles(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for profile in &self.profiles {
+            if let Ok(ProfileId::Builtin(builtin)) = ProfileId::parse(&profile.name) {
+                anyhow::bail!(
+                    "Profile name '{}' is reserved for the built-in {} profile; choose another name",
+                    profile.name,
+                    builtin.as_str()
+                );
+            }
+            if !names.insert(profile.name.as_str()) {
+                anyhow::bail!("Profile '{}' is declared more than once", profile.name);
+            }
+        }
+        Ok(())
     }
 
     /// Save config to file
@@ -75,37 +118,97 @@ impl Config {
         Ok(())
     }
 
-    /// Check if a system generation is pinned
-    pub fn is_system_pinned(&self, gen_id: u32) -> bool {
-        self.pinned.system.contains(&gen_id)
+    /// Check if a generation of the given profile is pinned
+    pub fn is_pinned(&self, profile: &ProfileId, gen_id: u32) -> bool {
+        self.pinned.profile(profile).is_some_and(|(pins, _)| pins.contains(&gen_id))
     }
 
-    /// Check if a home-manager generation is pinned
-    pub fn is_home_manager_pinned(&self, gen_id: u32) -> bool {
-        self.pinned.home_manager.contains(&gen_id)
+    /// Toggle pin status of a generation
+    pub fn toggle_pin(&mut self, profile: &ProfileId, gen_id: u32) {
+        if !self.unpin(profile, gen_id) {
+            self.pin(profile, gen_id, None);
+        }
     }
 
-    /// Toggle pin status for system generation
-    pub fn toggle_system_pin(&mut self, gen_id: u32) {
-        if self.pinned.system.contains(&gen_id) {
-            self.pinned.system.remove(&gen_id);
-        } else {
-            self.pinned.system.insert(gen_id);
+    /// Pin a generation, replacing its note if one is given
+    pub fn pin(&mut self, profile: &ProfileId, gen_id: u32, note: Option<String>) {
+        let (pins, notes) = self.pinned.profile_mut(profile);
+        pins.insert(gen_id);
+        if let Some(note) = note {
+            notes.insert(gen_id.to_string(), note);
         }
     }
 
-    /// Toggle pin status for home-manager generation
-    pub fn toggle_home_manager_pin(&mut self, gen_id: u32) {
-        if self.pinned.home_manager.contains(&gen_id) {
-            self.pinned.home_manager.remove(&gen_id);
-        } else {
-            self.pinned.home_manager.insert(gen_id);
+    /// Unpin a generation and drop its note, returning whether it was pinned
+    pub fn unpin(&mut self, profile: &ProfileId, gen_id: u32) -> bool {
+        let (pins, notes) = self.pinned.profile_mut(profile);
+        notes.remove(&gen_id.to_string());
+        pins.remove(&gen_id)
+    }
+
+    /// Pinned generation IDs of a profile, in ascending order
+    pub fn pinned_ids(&self, profile: &ProfileId) -> Vec<u32> {
+        let Some((pins, _)) = self.pinned.profile(profile) else {
+            return Vec::new();
+        };
+        let mut ids: Vec<u32> = pins.iter().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Note recorded when a generation was pinned
+    pub fn pin_note(&self, profile: &ProfileId, gen_id: u32) -> Option<&str> {
+        let (_, notes) = self.pinned.profile(profile)?;
+        notes.get(&gen_id.to_string()).map(String::as_str)
+    }
+
+    /// Profile declared under `[[profiles]]` with the given name
+    pub fn custom_profile(&self, name: &str) -> Option<&CustomProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+}
+
+/// A profile declared in config.toml, e.g. a per-user `profile` link or
+/// one made with `nix-env -p`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomProfile {
+    /// Name shown in the TUI and accepted by `--profile`
+    pub name: String,
+    /// The profile symlink (its generation links are siblings)
+    pub path: PathBuf,
+    /// How generations are read, restored and deleted
+    #[serde(default)]
+    pub kind: ProfileKind,
+}
+
+/// Kind of a configured profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfileKind {
+    /// Like the NixOS system profile (switch-to-configuration)
+    System,
+    /// Like a Home-Manager profile (activate script)
+    HomeManager,
+    /// A `nix profile` profile (manifest.json)
+    NixProfile,
+    /// Any other profile, switched and cleaned with `nix-env`
+    #[default]
+    NixEnv,
+}
+
+impl ProfileKind {
+    pub fn profile_type(self) -> ProfileType {
+        match self {
+            ProfileKind::System => ProfileType::System,
+            ProfileKind::HomeManager => ProfileType::HomeManager,
+            ProfileKind::NixProfile => ProfileType::NixProfile,
+            ProfileKind::NixEnv => ProfileType::NixEnv,
         }
     }
 }
 
 /// Available theme names
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum ThemeName {
     #[default]
@@ -115,10 +218,6 @@ pub enum ThemeName {
 }
 
 impl ThemeName {
-    pub fn all() -> &'static [ThemeName] {
-        &[ThemeName::Gruvbox, ThemeName::Nord, ThemeName::Transparent]
-    }
-
     pub fn as_str(&self) -> &'static str {
         match self {
             ThemeName::Gruvbox => "Gruvbox",
@@ -174,6 +273,8 @@ pub struct DisplayOptions {
     pub show_size: bool,
     pub show_store_path: bool,
     pub show_boot_entry: bool,
+    /// Also count the store paths in each closure (one extra Nix query per generation)
+    pub show_closure_count: bool,
 }
 
 impl Default for DisplayOptions {
@@ -185,6 +286,7 @@ impl Default for DisplayOptions {
             show_size: true,
             show_store_path: false,
             show_boot_entry: true,
+            show_closure_count: false,
         }
     }
 }
@@ -197,6 +299,104 @@ pub struct PinnedGenerations {
     pub system: HashSet<u32>,
     #[serde(default)]
     pub home_manager: HashSet<u32>,
+    /// Why a system generation was pinned, keyed by generation ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub system_notes: BTreeMap<String, String>,
+    /// Why a home-manager generation was pinned, keyed by generation ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub home_manager_notes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub nix_profile: HashSet<u32>,
+    /// Why a `nix profile` generation was pinned, keyed by generation ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nix_profile_notes: BTreeMap<String, String>,
+    /// Pins of profiles declared in config.toml, keyed by profile name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, ProfilePins>,
+}
+
+/// Pins of a profile declared in config.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfilePins {
+    pub generations: HashSet<u32>,
+    /// Why a generation was pinned, keyed by generation ID
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub notes: BTreeMap<String, String>,
+}
+
+impl PinnedGenerations {
+    /// Pin set and notes of a profile (None for a configured profile without pins)
+    fn profile(&self, profile: &ProfileId) -> Option<(&HashSet<u32>, &BTreeMap<String, String>)> {
+        match profile {
+            ProfileId::Builtin(ProfileType::System) => Some((&self.system, &self.system_notes)),
+            ProfileId::Builtin(ProfileType::HomeManager) => Some((&self.home_manager, &self.home_manager_notes)),
+            ProfileId::Builtin(ProfileType::NixProfile) => Some((&self.nix_profile, &self.nix_profile_notes)),
+            ProfileId::Builtin(ProfileType::NixEnv) => None,
+            ProfileId::Custom(name) => self
+                .custom
+                .get(name)
+                .map(|pins| (&pins.generations, &pins.notes)),
+        }
+    }
+
+    /// Mutable pin set and notes of a profile
+    fn profile_mut(&mut self, profile: &ProfileId) -> (&mut HashSet<u32>, &mut BTreeMap<String, String>) {
+        match profile {
+            ProfileId::Builtin(ProfileType::System) => (&mut self.system, &mut self.system_notes),
+            ProfileId::Builtin(ProfileType::HomeManager) => (&mut self.home_manager, &mut self.home_manager_notes),
+            ProfileId::Builtin(ProfileType::NixProfile) => (&mut self.nix_profile, &mut self.nix_profile_notes),
+            ProfileId::Builtin(ProfileType::NixEnv) => unreachable!("nix-env is a profile kind, not a profile"),
+            ProfileId::Custom(name) => {
+                let pins = self.custom.entry(name.clone()).or_default();
+                (&mut pins.generations, &mut pins.notes)
+            }
+        }
+    }
+}
+
+/// Retention rules evaluated by `nixhist gc` and the Manage tab
+///
+/// A generation is kept if any rule keeps it. Without any `keep_*`
+/// count/age rule the policy is disabled and nothing is deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep the N newest generations
+    pub keep_last: Option<usize>,
+    /// Keep generations newer than D days
+    pub keep_days: Option<u32>,
+    /// Keep the newest generation of each of the last N weeks
+    pub keep_weekly: Option<usize>,
+    /// Keep the newest generation of each of the last N months
+    pub keep_monthly: Option<usize>,
+    /// Always keep pinned generations
+    pub keep_pinned: bool,
+    /// Always keep generations listed in the bootloader
+    pub keep_bootloader: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: None,
+            keep_days: None,
+            keep_weekly: None,
+            keep_monthly: None,
+            keep_pinned: true,
+            keep_bootloader: true,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Check if any rule that selects generations for deletion is set
+    pub fn is_enabled(&self) -> bool {
+        self.keep_last.is_some()
+            || self.keep_days.is_some()
+            || self.keep_weekly.is_some()
+            || self.keep_monthly.is_some()
+    }
 }
 
 #[cfg(test)]
@@ -214,13 +414,93 @@ mod tests {
     #[test]
     fn test_pin_toggle() {
         let mut config = Config::default();
-        assert!(!config.is_system_pinned(42));
+        assert!(!config.is_pinned(&ProfileId::SYSTEM, 42));
         
-        config.toggle_system_pin(42);
-        assert!(config.is_system_pinned(42));
+        config.toggle_pin(&ProfileId::SYSTEM, 42);
+        assert!(config.is_pinned(&ProfileId::SYSTEM, 42));
+        assert!(!config.is_pinned(&ProfileType::NixProfile.into(), 42));
         
-        config.toggle_system_pin(42);
-        assert!(!config.is_system_pinned(42));
+        config.toggle_pin(&ProfileId::SYSTEM, 42);
+        assert!(!config.is_pinned(&ProfileId::SYSTEM, 42));
+    }
+
+    #[test]
+    fn test_pin_notes_roundtrip() {
+        let hm = ProfileId::Builtin(ProfileType::HomeManager);
+        let mut config = Config::default();
+        config.pin(&hm, 85, Some("before plasma 6".into()));
+        config.pin(&ProfileId::SYSTEM, 140, None);
+
+        let toml = toml::to_string_pretty(&config).unwrap();
+        let mut config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config.pinned_ids(&hm), vec![85]);
+        assert_eq!(config.pin_note(&hm, 85), Some("before plasma 6"));
+        assert_eq!(config.pin_note(&ProfileId::SYSTEM, 140), None);
+
+        assert!(config.unpin(&hm, 85));
+        assert!(!config.unpin(&hm, 85));
+        assert_eq!(config.pin_note(&hm, 85), None);
+    }
+
+    #[test]
+    fn test_custom_profiles() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[profiles]]
+            name = "dev-tools"
+            path = "/nix/var/nix/profiles/per-user/alice/dev-tools"
+
+            [[profiles]]
+            name = "server"
+            path = "/nix/var/nix/profiles/server"
+            kind = "system"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.custom_profile("dev-tools").unwrap().kind, ProfileKind::NixEnv);
+        assert_eq!(config.custom_profile("server").unwrap().kind, ProfileKind::System);
+
+        let server = ProfileId::Custom("server".into());
+        config.pin(&server, 7, Some("last known good".into()));
+
+        let toml = toml::to_string_pretty(&config).unwrap();
+        let config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config.profiles.len(), 2);
+        assert_eq!(config.pinned_ids(&server), vec![7]);
+        assert_eq!(config.pin_note(&server, 7), Some("last known good"));
+        assert!(!config.is_pinned(&ProfileId::Custom("dev-tools".into()), 7));
+        assert!(!config.is_pinned(&ProfileId::SYSTEM, 7));
+    }
+
+    #[test]
+    fn test_rejects_reserved_and_duplicate_profile_names() {
+        let profiles = |names: &[&str]| Config {
+            profiles: names
+                .iter()
+                .map(|name| CustomProfile { name: name.to_string(), path: "/p".into(), kind: ProfileKind::NixEnv })
+                .collect(),
+            ..Config::default()
+        };
+
+        assert!(profiles(&["dev-tools", "server", "nix-env"]).check_profiles().is_ok());
+        assert_eq!(ProfileId::parse("nix-env"), Ok(ProfileId::Custom("nix-env".into())));
+        for reserved in ["system", "Home-Manager", "NIX-PROFILE"] {
+            let err = profiles(&["dev-tools", reserved]).check_profiles().unwrap_err();
+            assert!(err.to_string().contains("reserved"), "{}", reserved);
+        }
+        let err = profiles(&["server", "server"]).check_profiles().unwrap_err();
+        assert!(err.to_string().contains("more than once"));
+    }
+
+    #[test]
+    fn test_retention_config() {
+        let config: Config = toml::from_str("[retention]\nkeep_last = 5\nkeep_monthly = 6\n").unwrap();
+        assert_eq!(config.retention.keep_last, Some(5));
+        assert_eq!(config.retention.keep_monthly, Some(6));
+        assert!(config.retention.keep_pinned);
+        assert!(config.retention.is_enabled());
+
+        assert!(!Config::default().retention.is_enabled());
     }
 
     #[test]
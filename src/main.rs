@@ -8,17 +8,29 @@
 //! - Restore to previous generations
 //! - Delete old generations (with undo countdown)
 //! - Pin important generations
+//! - Headless listing and diffing for scripts (`nixhist list`, `nixhist diff`)
+//! - Retention policies (`nixhist gc`)
+//! - Pin management for provisioning scripts (`nixhist pin`)
+//! - Markdown/HTML change reports (`nixhist report`, `e` in the Diff tab)
+//! - Metadata cache per store path (`--no-cache`, `nixhist cache clear`)
 //!
-//! Usage: nixhist [--dry-run]
+//! Usage: nixhist [OPTIONS] [COMMAND] (see `nixhist --help`)
 
 mod app;
+mod cli;
 mod config;
 mod nix;
+mod policy;
+mod report;
+mod tasks;
 mod types;
 mod ui;
 
 use anyhow::{Context, Result};
 use app::App;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
+use cli::{Cli, Command, GlobalArgs};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEventKind},
     execute,
@@ -29,74 +41,61 @@ use std::io::stdout;
 use std::time::Duration;
 
 fn main() -> Result<()> {
-    // Parse arguments
-    let args: Vec<String> = std::env::args().collect();
-    let dry_run = args.iter().any(|a| a == "--dry-run" || a == "-n");
-
-    if args.iter().any(|a| a == "--help" || a == "-h") {
-        print_help();
-        return Ok(());
+    // Answer shell completion requests (`COMPLETE=<shell> nixhist -- ...`)
+    CompleteEnv::with_factory(Cli::command)
+        .var(cli::completions::COMPLETE_VAR)
+        .complete();
+
+    // Parse arguments. Usage errors exit with 1 because codes from 2 up
+    // are reserved for `nixhist diff` results.
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+
+    if let Some(path) = &cli.global.config {
+        config::Config::set_path_override(path.clone());
     }
-
-    if args.iter().any(|a| a == "--version" || a == "-v") {
-        println!("nixhist {}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
+    if cli.global.no_cache {
+        nix::cache::MetadataCache::disable();
     }
 
-    // Run the application
-    let result = run_app(dry_run);
+    // Headless subcommands print and exit without touching the terminal
+    let backend = nix::SystemBackend;
+    let result = match &cli.command {
+        Some(Command::List(args)) => cli::list::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Diff(args)) => cli::diff::run(&cli.global, &backend, args),
+        Some(Command::Packages(args)) => cli::packages::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Gc(args)) => cli::gc::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Pin(args)) => cli::pin::run_pin(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Unpin(args)) => cli::pin::run_unpin(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Pins(args)) => cli::pin::run_pins(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Why(args)) => cli::why::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::History(args)) => cli::history::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Report(args)) => cli::report::run(&cli.global, &backend, args).map(|()| 0),
+        Some(Command::Cache(args)) => cli::cache::run(args).map(|()| 0),
+        Some(Command::Completions(args)) => cli::completions::run_completions(args).map(|()| 0),
+        Some(Command::Manpage) => cli::completions::run_manpage().map(|()| 0),
+        None => run_app(&cli.global).map(|()| 0),
+    };
 
     // Always try to restore terminal state, even on error
-    if let Err(e) = result {
-        eprintln!("Error: {:#}", e);
-        std::process::exit(1);
+    match result {
+        Ok(0) => Ok(()),
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
     }
-
-    Ok(())
 }
 
-fn print_help() {
-    println!(
-        r#"nixhist - NixOS Generation Dashboard
-
-USAGE:
-    nixhist [OPTIONS]
-
-OPTIONS:
-    -n, --dry-run    Show what would be done without executing
-    -h, --help       Print help information
-    -v, --version    Print version information
-
-KEYBINDINGS:
-    1-5              Switch tabs
-    j/k              Navigate up/down
-    Tab              Switch panel/focus
-    Enter            Select/confirm
-    Space            Toggle selection (Manage tab)
-    R                Restore generation
-    D                Delete generation(s)
-    P                Pin/unpin generation
-    /                Filter (Packages tab)
-    q                Quit
-
-TABS:
-    [1] Overview     View all generations
-    [2] Packages     Browse packages in a generation
-    [3] Diff         Compare two generations
-    [4] Manage       Restore, delete, pin generations
-    [5] Settings     Configure theme and display options
-
-CONFIG:
-    ~/.config/nixhist/config.toml
-"#
-    );
-}
+fn run_app(global: &GlobalArgs) -> Result<()> {
+    let dry_run = global.dry_run;
 
-fn run_app(dry_run: bool) -> Result<()> {
     // Detect system configuration
     eprintln!("Detecting system configuration...");
-    let system_info = nix::detect_system()
-        .context("Failed to detect system configuration")?;
+    let system_info = global.system_info()?;
 
     eprintln!("Hostname: {}", system_info.hostname);
     eprintln!("Uses flakes: {}", system_info.uses_flakes);
@@ -110,13 +109,17 @@ fn run_app(dry_run: bool) -> Result<()> {
     );
 
     // Load configuration
-    let config = config::Config::load()
-        .context("Failed to load configuration")?;
+    let config = global.config()?;
 
-    // Create application state
-    eprintln!("Loading generations...");
-    let mut app = App::new(system_info, config, dry_run)
-        .context("Failed to initialize application")?;
+    // Create application state (generations load in the background)
+    let mut app = App::new(system_info, config, dry_run);
+
+    if let Some(profile) = &global.profile {
+        app.select_profile(profile);
+    }
+    if let Some(tab) = global.start_tab {
+        app.active_tab = tab;
+    }
 
     if dry_run {
         eprintln!("Running in dry-run mode (no changes will be made)");
@@ -158,6 +161,9 @@ fn main_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()
         // Update undo timer if active
         app.update_undo_timer()?;
 
+        // Apply progress and results of background loading
+        app.poll_tasks();
+
         // Poll for events with timeout (for timer updates)
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
@@ -176,13 +182,3 @@ fn main_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()
 
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_help_does_not_panic() {
-        print_help();
-    }
-}
@@ -0,0 +1,127 @@
+//! Backend abstraction over Nix
+//!
+//! Everything that runs `nix-env`, `nix path-info` or `sudo` goes through
+//! `NixBackend`, so the application logic can run against fixture data
+//! (see `nix::fake`) instead of a real NixOS machine.
+
+use crate::nix::closure::{self, ClosureGraph};
+use crate::nix::{cache, commands, generations, packages, why, CommandResult, GenerationSource, WhyNode};
+use crate::types::{Generation, Package, ProfileType};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Operations that query or modify Nix profiles
+///
+/// Backends are shared with the background workers (see `tasks`).
+pub trait NixBackend: Send + Sync {
+    /// List all generations of a profile
+    fn list_generations(&self, source: &GenerationSource) -> Result<Vec<Generation>> {
+        self.list_generations_with_progress(source, &mut |_, _| {})
+    }
+
+    /// List all generations of a profile, calling `progress(done, total)`
+    /// before each generation is read
+    fn list_generations_with_progress(
+        &self,
+        source: &GenerationSource,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Vec<Generation>>;
+
+    /// Get all packages in a generation
+    fn get_packages(&self, gen_path: &Path) -> Result<Vec<Package>>;
+
+    /// Count the store paths in the closures of generations, all at once
+    /// (generations whose closure could not be read are left out)
+    fn closure_counts(&self, gen_paths: &[PathBuf]) -> HashMap<PathBuf, usize>;
+
+    /// Size in bytes of the closure of a generation or store path
+    fn closure_size(&self, path: &Path) -> Result<u64>;
+
+    /// Every store path in the closure of a generation or store path
+    fn closure_graph(&self, path: &Path) -> Result<ClosureGraph>;
+
+    /// Store path that a path (e.g. `./result`) is or links to, if any
+    fn store_path(&self, path: &Path) -> Option<String>;
+
+    /// Explain why packages are in a generation's closure
+    fn why_depends(&self, gen_path: &Path, packages: &[&str]) -> Result<Option<WhyNode>>;
+
+    /// Restore (switch to) a generation, or one of its specialisations
+    fn restore_generation(
+        &self,
+        profile_path: &Path,
+        generation_id: u32,
+        specialisation: Option<&str>,
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult>;
+
+    /// Delete generations
+    fn delete_generations(
+        &self,
+        profile_path: &Path,
+        generation_ids: &[u32],
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult>;
+}
+
+/// Backend running the real Nix commands
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemBackend;
+
+impl NixBackend for SystemBackend {
+    fn list_generations_with_progress(
+        &self,
+        source: &GenerationSource,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Vec<Generation>> {
+        generations::list_generations(source, progress)
+    }
+
+    fn get_packages(&self, gen_path: &Path) -> Result<Vec<Package>> {
+        packages::get_packages(gen_path)
+    }
+
+    fn closure_counts(&self, gen_paths: &[PathBuf]) -> HashMap<PathBuf, usize> {
+        generations::get_closure_counts(gen_paths)
+    }
+
+    fn closure_size(&self, path: &Path) -> Result<u64> {
+        generations::get_closure_size(path)
+    }
+
+    fn closure_graph(&self, path: &Path) -> Result<ClosureGraph> {
+        closure::get_closure_graph(path)
+    }
+
+    fn store_path(&self, path: &Path) -> Option<String> {
+        cache::store_path_of(path)
+    }
+
+    fn why_depends(&self, gen_path: &Path, packages: &[&str]) -> Result<Option<WhyNode>> {
+        why::why_depends(gen_path, packages)
+    }
+
+    fn restore_generation(
+        &self,
+        profile_path: &Path,
+        generation_id: u32,
+        specialisation: Option<&str>,
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult> {
+        commands::restore_generation(profile_path, generation_id, specialisation, profile_type, dry_run)
+    }
+
+    fn delete_generations(
+        &self,
+        profile_path: &Path,
+        generation_ids: &[u32],
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult> {
+        commands::delete_generations(profile_path, generation_ids, profile_type, dry_run)
+    }
+}
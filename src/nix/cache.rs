@@ -0,0 +1,178 @@
+//! Persistent metadata cache
+//!
+//! Store paths are immutable, so everything derived from one (closure size,
+//! versions, package list) can be kept across runs. Entries live in
+//! `$XDG_CACHE_HOME/nixhist/v<N>/`, one JSON file per store path. Bumping
+//! `CACHE_VERSION` invalidates all entries written by older releases.
+
+use crate::types::Package;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Format version of cache entries; bump when `CacheEntry` changes meaning
+const CACHE_VERSION: u32 = 3;
+
+/// Set by `--no-cache`
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Serializes updates within the process (background tasks update concurrently)
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Numbers the temporary files of this process
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Cached metadata of one store path (fields are filled in as they are read)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheEntry {
+    pub closure_size: Option<u64>,
+    /// Number of store paths in the closure
+    pub closure_count: Option<usize>,
+    /// NixOS/Home-Manager and kernel version (either may be unknown)
+    pub versions: Option<(Option<String>, Option<String>)>,
+    pub package_count: Option<usize>,
+    pub packages: Option<Vec<Package>>,
+}
+
+/// On-disk cache of store path metadata
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+    dir: PathBuf,
+}
+
+impl MetadataCache {
+    /// Bypass the cache for the rest of the process (`--no-cache`)
+    pub fn disable() {
+        DISABLED.store(true, Ordering::Relaxed);
+    }
+
+    /// The user's cache, unless disabled or there is no cache directory
+    pub fn open() -> Option<Self> {
+        if DISABLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        let dir = root_dir()?.join(format!("v{}", CACHE_VERSION));
+        Some(Self { dir })
+    }
+
+    /// Cached metadata of a store path
+    pub fn get(&self, store_path: &str) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.entry_path(store_path)?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Update the cached metadata of a store path
+    ///
+    /// Failures are ignored: the cache only saves time.
+    pub fn update(&self, store_path: &str, f: impl FnOnce(&mut CacheEntry)) {
+        let Some(path) = self.entry_path(store_path) else {
+            return;
+        };
+        let _guard = UPDATE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entry = self.get(store_path).unwrap_or_default();
+        f(&mut entry);
+
+        let Ok(json) = serde_json::to_string(&entry) else {
+            return;
+        };
+        // Write to a temporary file first so readers never see half an entry;
+        // the name is unique per writer, as other processes may update too
+        let tmp = path.with_extension(format!(
+            "tmp{}-{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if fs::create_dir_all(&self.dir).is_ok() && fs::write(&tmp, json).is_ok() {
+            let _ = fs::rename(&tmp, &path);
+        }
+    }
+
+    /// File of a store path's entry (None for paths outside the Nix store)
+    fn entry_path(&self, store_path: &str) -> Option<PathBuf> {
+        let name = store_path.strip_prefix("/nix/store/")?;
+        if name.is_empty() || name.contains('/') {
+            return None;
+        }
+        Some(self.dir.join(format!("{}.json", name)))
+    }
+}
+
+/// Store path a generation link points to, as used for cache keys
+pub fn store_path_of(gen_path: &Path) -> Option<String> {
+    let target = fs::canonicalize(gen_path).ok()?;
+    let target = target.to_str()?;
+    target.starts_with("/nix/store/").then(|| target.to_string())
+}
+
+/// `$XDG_CACHE_HOME/nixhist` (or `~/.cache/nixhist`)
+fn root_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("nixhist"))
+}
+
+/// Delete all cached metadata (every cache version); returns the removed directory
+pub fn clear() -> Result<Option<PathBuf>> {
+    let Some(dir) = root_dir() else {
+        return Ok(None);
+    };
+    if !dir.exists() {
+        return Ok(None);
+    }
+
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {:?}", dir))?;
+    Ok(Some(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("nixhist-cache-test-{}", std::process::id()));
+        let cache = MetadataCache { dir: dir.clone() };
+        let path = "/nix/store/aaaa-nixos-system-host-24.05";
+
+        assert_eq!(cache.get(path), None);
+        cache.update(path, |entry| entry.closure_size = Some(9000));
+        cache.update(path, |entry| entry.versions = Some((Some("24.05".into()), None)));
+
+        let entry = cache.get(path).unwrap();
+        assert_eq!(entry.closure_size, Some(9000));
+        assert_eq!(entry.versions, Some((Some("24.05".into()), None)));
+        assert_eq!(entry.packages, None);
+
+        // Only top-level store paths are cached
+        cache.update("/home/alice/result", |entry| entry.closure_size = Some(1));
+        cache.update("/nix/store/aaaa-x/bin", |entry| entry.closure_size = Some(1));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let dir = std::env::temp_dir().join(format!("nixhist-cache-concurrent-{}", std::process::id()));
+        let cache = MetadataCache { dir: dir.clone() };
+        let path = "/nix/store/aaaa-nixos-system-host-24.05";
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        cache.update(path, |entry| entry.closure_count = Some(entry.closure_count.unwrap_or(0) + 1));
+                    }
+                });
+            }
+        });
+
+        // No update was lost and no temporary file was left behind
+        assert_eq!(cache.get(path).unwrap().closure_count, Some(160));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+//! Full-closure diffs
+//!
+//! Compares every store path in two closures instead of the top-level
+//! packages, so packages rebuilt with an unchanged version (same name and
+//! version, new hash) show up as well. The cause of a rebuild is found by
+//! following the references that changed down to the dependencies whose
+//! version changed, or that were rebuilt without any changed reference.
+//! Paths without a version (configuration files, wrappers, `source`) are
+//! not listed, but are followed when looking for causes.
+
+use crate::nix::cache::store_path_of;
+use crate::nix::drv_name::parse_store_name;
+use crate::nix::path_info::{closure_info, PathInfo};
+use crate::types::{DiffSort, GenerationDiff, Package, PackageRebuild, PackageUpdate};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+/// Every store path of a closure with its size and references
+pub type ClosureGraph = HashMap<String, PathInfo>;
+
+/// Read the closure of a generation or any other store path
+pub fn get_closure_graph(path: &Path) -> Result<ClosureGraph> {
+    let store_path = store_path_of(path)
+        .with_context(|| format!("Not a store path: {:?}", path))?;
+    closure_info(&store_path)
+}
+
+/// (name, version, output) of a store path
+type PathKey = (String, String, String);
+
+fn path_key(path: &str) -> Option<PathKey> {
+    let name = parse_store_name(path)?;
+    Some((name.name, name.version, name.output))
+}
+
+/// Compare two closures path by path
+pub fn closure_diff(old: &ClosureGraph, new: &ClosureGraph) -> GenerationDiff {
+    let old_versions = versions(old);
+    let new_versions = versions(new);
+    let old_keys: HashSet<PathKey> = old.keys().filter_map(|path| path_key(path)).collect();
+
+    let mut diff = GenerationDiff::default();
+    let mut causes = Causes { old, new, old_keys: &old_keys, memo: HashMap::new() };
+
+    for (name, new_by_version) in &new_versions {
+        let Some(old_by_version) = old_versions.get(name) else {
+            diff.added.extend(new_by_version.iter().map(|(version, v)| v.package(name, version)));
+            continue;
+        };
+
+        let only_old: Vec<_> = old_by_version.iter().filter(|(v, _)| !new_by_version.contains_key(*v)).collect();
+        let only_new: Vec<_> = new_by_version.iter().filter(|(v, _)| !old_by_version.contains_key(*v)).collect();
+        match (only_old.as_slice(), only_new.as_slice()) {
+            ([(old_version, o)], [(new_version, n)]) => {
+                diff.updated.push(PackageUpdate::new(&o.package(name, old_version), &n.package(name, new_version)));
+            }
+            _ => {
+                diff.removed.extend(only_old.iter().map(|(version, v)| v.package(name, version)));
+                diff.added.extend(only_new.iter().map(|(version, v)| v.package(name, version)));
+            }
+        }
+
+        // Versions on both sides whose store paths changed were rebuilt
+        for (version, v) in new_by_version.iter().filter(|(v, _)| old_by_version.contains_key(*v)) {
+            let rebuilt: Vec<&str> = v.paths.iter().map(String::as_str).filter(|p| !old.contains_key(*p)).collect();
+            if rebuilt.is_empty() {
+                continue;
+            }
+            let caused_by: BTreeSet<String> = rebuilt.iter().flat_map(|p| causes.of(p)).collect();
+            diff.rebuilt.push(PackageRebuild {
+                name: name.clone(),
+                version: version.clone(),
+                caused_by: caused_by.into_iter().collect(),
+            });
+        }
+    }
+
+    for (name, old_by_version) in &old_versions {
+        if !new_versions.contains_key(name) {
+            diff.removed.extend(old_by_version.iter().map(|(version, v)| v.package(name, version)));
+        }
+    }
+
+    let size = |graph: &ClosureGraph| graph.values().map(|info| info.size).sum();
+    let mut diff = diff.with_closure_sizes(size(old), size(new));
+    diff.sort(DiffSort::Name);
+    diff
+}
+
+/// Store paths and total size of one version of a package (all outputs)
+#[derive(Default)]
+struct Version {
+    paths: Vec<String>,
+    size: u64,
+}
+
+impl Version {
+    fn package(&self, name: &str, version: &str) -> Package {
+        Package {
+            name: name.into(),
+            version: version.into(),
+            size: self.size,
+            attr_path: None,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+/// Versioned paths of a closure by package name and version
+fn versions(graph: &ClosureGraph) -> BTreeMap<String, BTreeMap<String, Version>> {
+    let mut versions: BTreeMap<String, BTreeMap<String, Version>> = BTreeMap::new();
+    for (path, info) in graph {
+        let Some((name, version, _)) = path_key(path) else {
+            continue;
+        };
+        if version.is_empty() {
+            continue;
+        }
+        let entry = versions.entry(name).or_default().entry(version).or_default();
+        entry.paths.push(path.clone());
+        entry.size += info.size;
+    }
+    versions
+}
+
+/// Finds (and remembers) why new store paths exist
+struct Causes<'a> {
+    old: &'a ClosureGraph,
+    new: &'a ClosureGraph,
+    old_keys: &'a HashSet<PathKey>,
+    memo: HashMap<String, Vec<String>>,
+}
+
+impl Causes<'_> {
+    /// Changed dependencies behind a new path (empty if none of its references changed)
+    fn of(&mut self, path: &str) -> Vec<String> {
+        if let Some(causes) = self.memo.get(path) {
+            return causes.clone();
+        }
+        // Store path graphs are acyclic apart from self-references
+        self.memo.insert(path.to_string(), Vec::new());
+
+        let references = self.new.get(path).map(|info| info.references.as_slice()).unwrap_or(&[]);
+        let mut causes = BTreeSet::new();
+        for reference in references.iter().filter(|r| *r != path && !self.old.contains_key(*r)) {
+            let Some(key) = path_key(reference) else {
+                continue;
+            };
+            // Rebuilt dependencies pass on their own causes
+            let inherited = if self.old_keys.contains(&key) { self.of(reference) } else { Vec::new() };
+            if inherited.is_empty() {
+                let (name, version, _) = key;
+                causes.insert(if version.is_empty() { name } else { format!("{} {}", name, version) });
+            } else {
+                causes.extend(inherited);
+            }
+        }
+
+        let causes: Vec<String> = causes.into_iter().collect();
+        self.memo.insert(path.to_string(), causes.clone());
+        causes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(paths: &[(&str, u64, &[&str])]) -> ClosureGraph {
+        paths
+            .iter()
+            .map(|(path, size, references)| {
+                let references = references.iter().map(|r| format!("/nix/store/{}", r)).collect();
+                (format!("/nix/store/{}", path), PathInfo { size: *size, references })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_closure_diff() {
+        let old = graph(&[
+            ("aaaa-system", 0, &["bbbb-firefox-122.0", "dddd-ripgrep-14.1.0", "eeee-git-2.44.0"]),
+            ("bbbb-firefox-122.0", 4000, &["cccc-openssl-3.0.13", "bbbb-firefox-122.0"]),
+            ("cccc-openssl-3.0.13", 1000, &[]),
+            ("dddd-ripgrep-14.1.0", 500, &[]),
+            ("eeee-git-2.44.0", 800, &["ffff-etc"]),
+            ("ffff-etc", 10, &[]),
+        ]);
+        let new = graph(&[
+            ("1111-system", 0, &["2222-firefox-122.0", "dddd-ripgrep-14.1.0", "5555-git-2.44.0"]),
+            ("2222-firefox-122.0", 4100, &["3333-openssl-3.0.14", "2222-firefox-122.0"]),
+            ("3333-openssl-3.0.14", 1000, &[]),
+            ("dddd-ripgrep-14.1.0", 500, &[]),
+            ("5555-git-2.44.0", 800, &["6666-etc"]),
+            ("6666-etc", 12, &[]),
+            ("7777-zlib-1.3.1", 100, &[]),
+        ]);
+
+        let diff = closure_diff(&old, &new);
+        assert_eq!(diff.added.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["zlib"]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.updated.len(), 1);
+        assert_eq!((diff.updated[0].old_version.as_str(), diff.updated[0].new_version.as_str()), ("3.0.13", "3.0.14"));
+
+        // firefox because openssl changed, git because an unversioned path changed
+        let rebuilt: Vec<(&str, Vec<String>)> =
+            diff.rebuilt.iter().map(|r| (r.name.as_str(), r.caused_by.clone())).collect();
+        assert_eq!(rebuilt, [
+            ("firefox", vec!["openssl 3.0.14".to_string()]),
+            ("git", vec!["etc".to_string()]),
+        ]);
+        assert_eq!(diff.closure_change, Some(202));
+    }
+
+    #[test]
+    fn test_rebuild_causes_follow_rebuilt_dependencies() {
+        let old = graph(&[
+            ("aaaa-app-1.0", 1, &["bbbb-libfoo-2.0"]),
+            ("bbbb-libfoo-2.0", 1, &["cccc-glibc-2.39"]),
+            ("cccc-glibc-2.39", 1, &[]),
+        ]);
+        let new = graph(&[
+            ("1111-app-1.0", 1, &["2222-libfoo-2.0"]),
+            ("2222-libfoo-2.0", 1, &["3333-glibc-2.40"]),
+            ("3333-glibc-2.40", 1, &[]),
+        ]);
+
+        let diff = closure_diff(&old, &new);
+        for rebuild in &diff.rebuilt {
+            assert_eq!(rebuild.caused_by, ["glibc 2.40"], "{}", rebuild.name);
+        }
+        assert_eq!(diff.rebuilt.len(), 2);
+    }
+}
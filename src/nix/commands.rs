@@ -3,6 +3,7 @@
 //! Handles executing Nix commands with proper error handling.
 //! Supports dry-run mode for safe testing.
 
+use crate::nix::generations::get_generation_path;
 use crate::types::ProfileType;
 use anyhow::{Context, Result};
 use std::path::Path;
@@ -17,23 +18,31 @@ pub struct CommandResult {
 }
 
 /// Restore (switch to) a specific generation
+///
+/// With a specialisation, a system generation is switched into that
+/// specialisation instead of its base configuration.
 pub fn restore_generation(
     profile_path: &Path,
     generation_id: u32,
+    specialisation: Option<&str>,
     profile_type: ProfileType,
     dry_run: bool,
 ) -> Result<CommandResult> {
-    let command = build_restore_command(profile_path, generation_id, profile_type);
+    let command = build_restore_command(profile_path, generation_id, specialisation, profile_type);
+    let target = match specialisation {
+        Some(name) => format!("generation {} ({})", generation_id, name),
+        None => format!("generation {}", generation_id),
+    };
     
     if dry_run {
         return Ok(CommandResult {
             success: true,
-            message: format!("Dry run: Would execute restore to generation {}", generation_id),
+            message: format!("Dry run: Would execute restore to {}", target),
             command,
         });
     }
 
-    execute_sudo_command(&command, &format!("restore generation {}", generation_id))
+    execute_sudo_command(&command, &format!("restore {}", target))
 }
 
 /// Delete one or more generations
@@ -68,32 +77,30 @@ pub fn delete_generations(
 fn build_restore_command(
     profile_path: &Path,
     generation_id: u32,
+    specialisation: Option<&str>,
     profile_type: ProfileType,
 ) -> String {
     match profile_type {
         ProfileType::System => {
             // For system, we need to switch-to-configuration
-            let gen_path = profile_path
-                .parent()
-                .unwrap_or(Path::new("/nix/var/nix/profiles"))
-                .join(format!("system-{}-link", generation_id));
-            
+            let mut config_path = get_generation_path(profile_path, generation_id, profile_type);
+            if let Some(name) = specialisation {
+                // Like `nixos-rebuild switch --specialisation <name>`
+                config_path = config_path.join("specialisation").join(name);
+            }
+
             format!(
                 "sudo {}/bin/switch-to-configuration switch",
-                gen_path.display()
+                config_path.display()
             )
         }
         ProfileType::HomeManager => {
             // For home-manager, activate the generation
-            let home = std::env::var("HOME").unwrap_or_default();
-            let gen_path = format!(
-                "{}/.local/state/home-manager/profiles/home-manager-{}-link",
-                home, generation_id
-            );
-            
+            let gen_path = get_generation_path(profile_path, generation_id, profile_type);
+
             // Check if standalone or module
-            if Path::new(&gen_path).exists() {
-                format!("{}/activate", gen_path)
+            if gen_path.exists() {
+                format!("{}/activate", gen_path.display())
             } else {
                 // Module installation - use nix-env
                 format!(
@@ -103,6 +110,20 @@ fn build_restore_command(
                 )
             }
         }
+        ProfileType::NixEnv => {
+            format!(
+                "nix-env --switch-generation {} --profile {}",
+                generation_id,
+                profile_path.display()
+            )
+        }
+        ProfileType::NixProfile => {
+            format!(
+                "nix profile rollback --profile {} --to {}",
+                profile_path.display(),
+                generation_id
+            )
+        }
     }
 }
 
@@ -135,6 +156,14 @@ fn build_delete_command(
                 )
             }
         }
+        ProfileType::NixProfile | ProfileType::NixEnv => {
+            // `nix profile wipe-history` only deletes by age
+            format!(
+                "nix-env --delete-generations {} --profile {}",
+                ids_joined,
+                profile_path.display()
+            )
+        }
     }
 }
 
@@ -201,9 +230,10 @@ fn execute_sudo_command(command: &str, description: &str) -> Result<CommandResul
 pub fn get_restore_command_preview(
     profile_path: &Path,
     generation_id: u32,
+    specialisation: Option<&str>,
     profile_type: ProfileType,
 ) -> String {
-    build_restore_command(profile_path, generation_id, profile_type)
+    build_restore_command(profile_path, generation_id, specialisation, profile_type)
 }
 
 /// Get the command that would be executed for delete (for display in confirmation)
@@ -230,10 +260,34 @@ mod tests {
         assert!(cmd.contains("141"));
     }
 
+    #[test]
+    fn test_nix_env_commands() {
+        let path = PathBuf::from("/nix/var/nix/profiles/per-user/alice/devtools");
+        let restore = build_restore_command(&path, 3, None, ProfileType::NixEnv);
+        assert_eq!(restore, format!("nix-env --switch-generation 3 --profile {}", path.display()));
+
+        let delete = build_delete_command(&path, &[1, 2], ProfileType::NixEnv);
+        assert!(!delete.contains("sudo"));
+        assert!(delete.contains("--delete-generations 1 2"));
+    }
+
+    #[test]
+    fn test_system_restore_uses_profile_links() {
+        let path = PathBuf::from("/tmp/profiles/system");
+        let cmd = build_restore_command(&path, 140, None, ProfileType::System);
+        assert_eq!(cmd, "sudo /tmp/profiles/system-140-link/bin/switch-to-configuration switch");
+
+        let cmd = build_restore_command(&path, 140, Some("gaming"), ProfileType::System);
+        assert_eq!(
+            cmd,
+            "sudo /tmp/profiles/system-140-link/specialisation/gaming/bin/switch-to-configuration switch"
+        );
+    }
+
     #[test]
     fn test_dry_run_restore() {
         let path = PathBuf::from("/nix/var/nix/profiles/system");
-        let result = restore_generation(&path, 140, ProfileType::System, true).unwrap();
+        let result = restore_generation(&path, 140, None, ProfileType::System, true).unwrap();
         assert!(result.success);
         assert!(result.message.contains("Dry run"));
     }
@@ -3,7 +3,8 @@
 //! Detects:
 //! - Whether the system uses Flakes or Channels
 //! - Whether Home-Manager is installed (standalone or as NixOS module)
-//! - Profile paths for both System and Home-Manager
+//! - Whether a `nix profile` user profile (manifest.json) exists
+//! - Profile paths for System, Home-Manager and `nix profile`
 
 use anyhow::{Context, Result};
 use std::env;
@@ -13,17 +14,18 @@ use std::path::PathBuf;
 #[derive(Debug, Clone)]
 pub struct SystemInfo {
     pub hostname: String,
-    pub username: String,
     pub uses_flakes: bool,
     pub system_profile: PathBuf,
     pub home_manager: Option<HomeManagerInfo>,
+    /// The `nix profile` user profile symlink, if one is in use
+    pub nix_profile: Option<PathBuf>,
 }
 
 /// Home-Manager installation info
 #[derive(Debug, Clone)]
 pub struct HomeManagerInfo {
+    /// The `home-manager` profile symlink (its generation links are siblings)
     pub profile_path: PathBuf,
-    pub is_standalone: bool,
 }
 
 /// Detect system configuration
@@ -36,13 +38,14 @@ pub fn detect_system() -> Result<SystemInfo> {
     let uses_flakes = detect_flakes();
     let system_profile = PathBuf::from("/nix/var/nix/profiles/system");
     let home_manager = detect_home_manager(&username);
+    let nix_profile = detect_nix_profile(&username);
 
     Ok(SystemInfo {
         hostname,
-        username,
         uses_flakes,
         system_profile,
         home_manager,
+        nix_profile,
     })
 }
 
@@ -105,8 +108,7 @@ fn detect_home_manager(username: &str) -> Option<HomeManagerInfo> {
 
     if standalone_path.exists() && has_generation_links(&standalone_path) {
         return Some(HomeManagerInfo {
-            profile_path: standalone_path,
-            is_standalone: true,
+            profile_path: standalone_path.join("home-manager"),
         });
     }
 
@@ -117,11 +119,8 @@ fn detect_home_manager(username: &str) -> Option<HomeManagerInfo> {
 
     // The module path is a symlink, check if it exists
     if module_path.exists() || module_path.is_symlink() {
-        // For module installation, we need the directory containing the links
-        let profile_dir = module_path.parent()?;
         return Some(HomeManagerInfo {
-            profile_path: profile_dir.to_path_buf(),
-            is_standalone: false,
+            profile_path: module_path,
         });
     }
 
@@ -135,8 +134,7 @@ fn detect_home_manager(username: &str) -> Option<HomeManagerInfo> {
             .join(".local/state/nix/profiles/home-manager");
         if alt_state.exists() {
             return Some(HomeManagerInfo {
-                profile_path: alt_state.parent()?.to_path_buf(),
-                is_standalone: true,
+                profile_path: alt_state,
             });
         }
     }
@@ -144,6 +142,27 @@ fn detect_home_manager(username: &str) -> Option<HomeManagerInfo> {
     None
 }
 
+/// Detect a `nix profile` user profile
+///
+/// Profiles managed by `nix profile` carry a `manifest.json`; classic
+/// `nix-env` profiles (`manifest.nix`) are not picked up.
+fn detect_nix_profile(username: &str) -> Option<PathBuf> {
+    let state_home = env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .ok();
+
+    let candidates = [
+        state_home.map(|dir| dir.join("nix/profiles/profile")),
+        Some(PathBuf::from("/nix/var/nix/profiles/per-user").join(username).join("profile")),
+    ];
+
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|path| path.join("manifest.json").exists())
+}
+
 /// Check if a directory contains generation links
 fn has_generation_links(path: &PathBuf) -> bool {
     if let Ok(entries) = std::fs::read_dir(path) {
@@ -0,0 +1,95 @@
+//! Store path name parsing
+//!
+//! Splits store path names into package name, version and output with the
+//! rules of Nix's `DrvName`: the name ends at the first dash that is not
+//! followed by a letter (`python3.11-foo-1.2` is `python3.11-foo` version
+//! `1.2`). Outputs other than `out` add a `-<output>` suffix to the path
+//! name (`git-2.45.1-man`), which is split off the version.
+
+/// Output suffixes that Nix appends to store path names
+const OUTPUTS: &[&str] = &[
+    "bin", "debug", "dev", "devdoc", "doc", "info", "lib", "man", "modules", "out", "py",
+    "python", "static", "terminfo",
+];
+
+/// Parsed name of a store path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreName {
+    pub name: String,
+    /// Empty if the path has no version
+    pub version: String,
+    /// `out` unless the path name ends in another output
+    pub output: String,
+}
+
+/// Split a derivation name into name and version, like Nix's `DrvName`
+pub fn split_drv_name(drv_name: &str) -> (&str, &str) {
+    let bytes = drv_name.as_bytes();
+    let split = (0..bytes.len().saturating_sub(1))
+        .find(|&i| bytes[i] == b'-' && !bytes[i + 1].is_ascii_alphabetic());
+
+    match split {
+        Some(i) => (&drv_name[..i], &drv_name[i + 1..]),
+        None => (drv_name, ""),
+    }
+}
+
+/// Parse a store path (or its base name) into name, version and output
+///
+/// Example: /nix/store/abc123-git-2.45.1-man -> ("git", "2.45.1", "man")
+pub fn parse_store_name(path: &str) -> Option<StoreName> {
+    let filename = path.rsplit('/').next()?;
+
+    // Remove the hash prefix (the hash itself never contains a dash)
+    let (_, drv_name) = filename.split_once('-')?;
+    if drv_name.is_empty() {
+        return None;
+    }
+
+    let (name, version) = split_drv_name(drv_name);
+    let (version, output) = match version.rsplit_once('-') {
+        Some((version, output)) if OUTPUTS.contains(&output) => (version, output),
+        _ => (version, "out"),
+    };
+
+    Some(StoreName {
+        name: name.to_string(),
+        version: version.to_string(),
+        output: output.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(path: &str) -> (String, String, String) {
+        let n = parse_store_name(path).unwrap();
+        (n.name, n.version, n.output)
+    }
+
+    #[test]
+    fn test_split_drv_name() {
+        assert_eq!(split_drv_name("firefox-122.0"), ("firefox", "122.0"));
+        assert_eq!(split_drv_name("python3.11-foo-1.2"), ("python3.11-foo", "1.2"));
+        assert_eq!(split_drv_name("font-2048"), ("font", "2048"));
+        assert_eq!(split_drv_name("x264-0-unstable-2024-01-01"), ("x264", "0-unstable-2024-01-01"));
+        assert_eq!(split_drv_name("nixos-system-host-24.05"), ("nixos-system-host", "24.05"));
+        assert_eq!(split_drv_name("system-path"), ("system-path", ""));
+        assert_eq!(split_drv_name("hello-"), ("hello-", ""));
+    }
+
+    #[test]
+    fn test_parse_store_name_outputs() {
+        let p = |s: &str| parse(&format!("/nix/store/abc123-{}", s));
+
+        assert_eq!(p("git-2.45.1"), ("git".into(), "2.45.1".into(), "out".into()));
+        assert_eq!(p("git-2.45.1-man"), ("git".into(), "2.45.1".into(), "man".into()));
+        assert_eq!(p("openssl-3.0.14-bin"), ("openssl".into(), "3.0.14".into(), "bin".into()));
+        assert_eq!(p("gcc-13.2.0-lib"), ("gcc".into(), "13.2.0".into(), "lib".into()));
+        // Only known outputs are split off
+        assert_eq!(p("foo-1.0-rc1"), ("foo".into(), "1.0-rc1".into(), "out".into()));
+        assert_eq!(p("etc"), ("etc".into(), "".into(), "out".into()));
+        assert!(parse_store_name("/nix/store/abc123").is_none());
+    }
+}
@@ -0,0 +1,302 @@
+//! In-memory `NixBackend` for tests
+//!
+//! Holds fixture generations and packages per profile and applies
+//! restores and deletions to that state (unless dry-running), so
+//! application flows can be tested end to end without Nix.
+
+use crate::nix::closure::ClosureGraph;
+use crate::nix::detect::HomeManagerInfo;
+use crate::nix::path_info::PathInfo;
+use crate::nix::{CommandResult, GenerationSource, NixBackend, SystemInfo, WhyNode};
+use crate::types::{Generation, Package, ProfileType};
+use anyhow::Result;
+use chrono::{Duration, Local, TimeZone};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Fixture-backed backend
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    /// Generations by profile path
+    generations: Mutex<HashMap<PathBuf, Vec<Generation>>>,
+    /// Packages by generation link path
+    packages: HashMap<PathBuf, Vec<Package>>,
+    /// Runtime dependencies by package name (for closure graphs)
+    dependencies: HashMap<String, Vec<String>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a generation with its packages
+    pub fn with_generation(mut self, source: &GenerationSource, gen: Generation, packages: Vec<Package>) -> Self {
+        self.packages.insert(source.generation_path(gen.id), packages);
+        let list = self
+            .generations
+            .get_mut()
+            .unwrap()
+            .entry(source.profile_path.clone())
+            .or_default();
+        list.push(gen);
+        list.sort_by_key(|g| std::cmp::Reverse(g.id));
+        self
+    }
+
+    /// Add a NixOS specialisation with its packages to a generation
+    pub fn with_specialisation(mut self, source: &GenerationSource, gen_id: u32, name: &str, packages: Vec<Package>) -> Self {
+        self.packages.insert(source.configuration_path(gen_id, Some(name)), packages);
+        let generations = self.generations.get_mut().unwrap();
+        if let Some(gen) = generations
+            .get_mut(&source.profile_path)
+            .and_then(|list| list.iter_mut().find(|g| g.id == gen_id))
+        {
+            gen.specialisations.push(name.into());
+        }
+        self
+    }
+
+    /// Make a package depend on another one in closure graphs
+    pub fn with_dependency(mut self, name: &str, dependency: &str) -> Self {
+        self.dependencies.entry(name.into()).or_default().push(dependency.into());
+        self
+    }
+
+    /// Store path of a package in a package set, hashed over its
+    /// dependencies like a real derivation
+    fn store_path(&self, package: &Package, packages: &[Package]) -> String {
+        let mut hasher = DefaultHasher::new();
+        (&package.name, &package.version).hash(&mut hasher);
+        for dependency in self.package_dependencies(package, packages) {
+            self.store_path(dependency, packages).hash(&mut hasher);
+        }
+        format!("/nix/store/{:016x}-{}-{}", hasher.finish(), package.name, package.version)
+    }
+
+    /// Dependencies of a package that are part of the same package set
+    fn package_dependencies<'a>(&self, package: &Package, packages: &'a [Package]) -> Vec<&'a Package> {
+        let names = self.dependencies.get(&package.name).map(Vec::as_slice).unwrap_or(&[]);
+        packages.iter().filter(|p| names.contains(&p.name)).collect()
+    }
+
+    /// A system with generations 140-142 (current, with a `gaming`
+    /// specialisation), Home-Manager generations 85-86 (current) and
+    /// `nix profile` generations 3-4 (current); firefox depends on openssl
+    pub fn fixture() -> (SystemInfo, Self) {
+        let info = system_info();
+        let system = GenerationSource::system(&info);
+        let hm = GenerationSource::home_manager(&info).expect("fixture has Home-Manager");
+        let profile = GenerationSource::nix_profile(&info).expect("fixture has a nix profile");
+
+        let backend = Self::new()
+            .with_generation(&system, generation(140, false), vec![
+                package("linux", "6.6.50"),
+                package("firefox", "121.0"),
+                package("openssl", "3.0.13"),
+            ])
+            .with_generation(&system, generation(141, false), vec![
+                package("linux", "6.6.51"),
+                package("firefox", "122.0"),
+                package("openssl", "3.0.13"),
+            ])
+            .with_generation(&system, generation(142, true), vec![
+                package("linux", "6.6.52"),
+                package("firefox", "122.0"),
+                package("openssl", "3.0.14"),
+                package("ripgrep", "14.1.0"),
+            ])
+            .with_specialisation(&system, 142, "gaming", vec![
+                package("linux", "6.6.52"),
+                package("firefox", "122.0"),
+                package("openssl", "3.0.14"),
+                package("ripgrep", "14.1.0"),
+                package("steam", "1.0.0.79"),
+            ])
+            .with_generation(&hm, generation(85, false), vec![package("git", "2.44.0")])
+            .with_generation(&hm, generation(86, true), vec![package("git", "2.45.1")])
+            .with_generation(&profile, generation(3, false), vec![package("ripgrep", "14.0.3")])
+            .with_generation(&profile, generation(4, true), vec![package("ripgrep", "14.1.0")])
+            .with_dependency("firefox", "openssl");
+
+        (info, backend)
+    }
+}
+
+impl NixBackend for FakeBackend {
+    fn list_generations_with_progress(
+        &self,
+        source: &GenerationSource,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Vec<Generation>> {
+        let generations = self
+            .generations
+            .lock()
+            .unwrap()
+            .get(&source.profile_path)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No such profile: {:?}", source.profile_path))?;
+        for done in 0..generations.len() {
+            progress(done, generations.len());
+        }
+        Ok(generations)
+    }
+
+    fn get_packages(&self, gen_path: &Path) -> Result<Vec<Package>> {
+        Ok(self.packages.get(gen_path).cloned().unwrap_or_default())
+    }
+
+    fn closure_counts(&self, gen_paths: &[PathBuf]) -> HashMap<PathBuf, usize> {
+        // The generation itself plus its packages
+        gen_paths
+            .iter()
+            .filter_map(|path| Some((path.clone(), self.packages.get(path)?.len() + 1)))
+            .collect()
+    }
+
+    fn closure_size(&self, path: &Path) -> Result<u64> {
+        Ok(self.get_packages(path)?.iter().map(|p| p.size).sum())
+    }
+
+    fn closure_graph(&self, path: &Path) -> Result<ClosureGraph> {
+        // The generation references its packages, which reference their dependencies
+        let packages = self.get_packages(path)?;
+        let mut graph: ClosureGraph = packages
+            .iter()
+            .map(|p| {
+                let references = self
+                    .package_dependencies(p, &packages)
+                    .into_iter()
+                    .map(|d| self.store_path(d, &packages))
+                    .collect();
+                (self.store_path(p, &packages), PathInfo { size: p.size, references })
+            })
+            .collect();
+        let references = graph.keys().cloned().collect();
+        graph.insert(path.display().to_string(), PathInfo { size: 0, references });
+        Ok(graph)
+    }
+
+    fn store_path(&self, path: &Path) -> Option<String> {
+        // Generation links and specialisations stand in for store paths
+        self.packages.contains_key(path).then(|| path.display().to_string())
+    }
+
+    fn why_depends(&self, gen_path: &Path, packages: &[&str]) -> Result<Option<WhyNode>> {
+        // Flat tree: the generation references its packages directly
+        let children: Vec<WhyNode> = self
+            .get_packages(gen_path)?
+            .into_iter()
+            .filter(|p| packages.contains(&p.name.as_str()))
+            .map(|p| WhyNode {
+                path: format!("/nix/store/fake-{}-{}", p.name, p.version),
+                name: format!("{}-{}", p.name, p.version),
+                is_target: true,
+                repeated: false,
+                children: Vec::new(),
+            })
+            .collect();
+
+        Ok((!children.is_empty()).then(|| WhyNode {
+            path: gen_path.display().to_string(),
+            name: gen_path.display().to_string(),
+            is_target: false,
+            repeated: false,
+            children,
+        }))
+    }
+
+    fn restore_generation(
+        &self,
+        profile_path: &Path,
+        generation_id: u32,
+        specialisation: Option<&str>,
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult> {
+        let mut command = format!("restore {} {}", profile_type.as_str(), generation_id);
+        if let Some(name) = specialisation {
+            command.push_str(&format!(" ({})", name));
+        }
+        if !dry_run {
+            let mut generations = self.generations.lock().unwrap();
+            let list = generations
+                .get_mut(profile_path)
+                .ok_or_else(|| anyhow::anyhow!("No such profile: {:?}", profile_path))?;
+            anyhow::ensure!(
+                list.iter().any(|g| g.id == generation_id),
+                "Generation {} not found",
+                generation_id
+            );
+            for gen in list.iter_mut() {
+                gen.is_current = gen.id == generation_id;
+            }
+        }
+
+        Ok(CommandResult {
+            success: true,
+            message: format!("Restored generation {}", generation_id),
+            command,
+        })
+    }
+
+    fn delete_generations(
+        &self,
+        profile_path: &Path,
+        generation_ids: &[u32],
+        profile_type: ProfileType,
+        dry_run: bool,
+    ) -> Result<CommandResult> {
+        let ids: Vec<String> = generation_ids.iter().map(|id| id.to_string()).collect();
+        let command = format!("delete {} {}", profile_type.as_str(), ids.join(" "));
+        if !dry_run {
+            if let Some(list) = self.generations.lock().unwrap().get_mut(profile_path) {
+                list.retain(|g| !generation_ids.contains(&g.id));
+            }
+        }
+
+        Ok(CommandResult {
+            success: true,
+            message: format!("Deleted {} generation(s)", generation_ids.len()),
+            command,
+        })
+    }
+}
+
+/// System info matching the fixture profiles
+pub fn system_info() -> SystemInfo {
+    SystemInfo {
+        hostname: "fixture".into(),
+        uses_flakes: true,
+        system_profile: PathBuf::from("/nix/var/nix/profiles/system"),
+        home_manager: Some(HomeManagerInfo {
+            profile_path: PathBuf::from("/home/alice/.local/state/nix/profiles/home-manager"),
+        }),
+        nix_profile: Some(PathBuf::from("/home/alice/.local/state/nix/profiles/profile")),
+    }
+}
+
+/// Fixture generation created `id` hours after a fixed epoch
+pub fn generation(id: u32, is_current: bool) -> Generation {
+    Generation {
+        id,
+        date: Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::hours(i64::from(id)),
+        is_current,
+        nixos_version: Some("24.05".into()),
+        kernel_version: None,
+        package_count: 0,
+        closure_size: u64::from(id) * 1024 * 1024,
+        closure_count: None,
+        store_path: format!("/nix/store/fake-generation-{}", id),
+        is_pinned: false,
+        in_bootloader: false,
+        specialisations: Vec::new(),
+    }
+}
+
+/// Fixture package
+pub fn package(name: &str, version: &str) -> Package {
+    Package { name: name.into(), version: version.into(), size: 1024, attr_path: None, outputs: Vec::new() }
+}
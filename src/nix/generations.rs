@@ -1,28 +1,105 @@
 //! Generation listing and parsing
 //!
 //! Handles listing generations for both System and Home-Manager profiles.
-//! Parses generation metadata including version, kernel, size, etc.
-
-use crate::types::{Generation, ProfileType};
+//! Generations are read from the `<profile>-<id>-link` symlinks next to
+//! the profile, falling back to `nix-env --list-generations` only if
+//! there are none. Parses generation metadata including version, kernel,
+//! size, etc. Package counts are the top-level references of the
+//! generation's package environment (`sw`, `home-path`, the user
+//! environment), not an estimate from its `bin` directory.
+
+use crate::config::CustomProfile;
+use crate::nix::cache::{store_path_of, MetadataCache};
+use crate::nix::packages::parse_store_path;
+use crate::nix::path_info::{closure_counts, path_references, path_sizes, SizeKind};
+use crate::nix::{read_manifest, SystemInfo};
+use crate::types::{DiffSide, Generation, ProfileId, ProfileType};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local, TimeZone};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// Source of generations (which profile)
 #[derive(Debug, Clone)]
 pub struct GenerationSource {
+    pub id: ProfileId,
     pub profile_type: ProfileType,
     pub profile_path: PathBuf,
 }
 
+impl GenerationSource {
+    /// Source for the NixOS system profile
+    pub fn system(info: &SystemInfo) -> Self {
+        Self {
+            id: ProfileId::SYSTEM,
+            profile_type: ProfileType::System,
+            profile_path: info.system_profile.clone(),
+        }
+    }
+
+    /// Source for the Home-Manager profile, if Home-Manager was detected
+    pub fn home_manager(info: &SystemInfo) -> Option<Self> {
+        info.home_manager.as_ref().map(|hm| Self {
+            id: ProfileType::HomeManager.into(),
+            profile_type: ProfileType::HomeManager,
+            profile_path: hm.profile_path.clone(),
+        })
+    }
+
+    /// Source for the `nix profile` user profile, if one was detected
+    pub fn nix_profile(info: &SystemInfo) -> Option<Self> {
+        info.nix_profile.as_ref().map(|path| Self {
+            id: ProfileType::NixProfile.into(),
+            profile_type: ProfileType::NixProfile,
+            profile_path: path.clone(),
+        })
+    }
+
+    /// Source for a profile declared in config.toml
+    pub fn custom(profile: &CustomProfile) -> Self {
+        Self {
+            id: ProfileId::Custom(profile.name.clone()),
+            profile_type: profile.kind.profile_type(),
+            profile_path: profile.path.clone(),
+        }
+    }
+
+    /// Path of the `<profile>-<id>-link` symlink for a generation
+    pub fn generation_path(&self, id: u32) -> PathBuf {
+        get_generation_path(&self.profile_path, id, self.profile_type)
+    }
+
+    /// Path of a generation, or of one of its NixOS specialisations
+    pub fn configuration_path(&self, id: u32, specialisation: Option<&str>) -> PathBuf {
+        let gen_path = self.generation_path(id);
+        match specialisation {
+            Some(name) => gen_path.join("specialisation").join(name),
+            None => gen_path,
+        }
+    }
+
+    /// Path whose packages make up one side of a diff
+    pub fn side_path(&self, side: &DiffSide) -> PathBuf {
+        match side {
+            DiffSide::Generation { id, specialisation } => self.configuration_path(*id, specialisation.as_deref()),
+            DiffSide::Path(path) => path.clone(),
+        }
+    }
+}
+
 /// List all generations for a given profile
-pub fn list_generations(source: &GenerationSource) -> Result<Vec<Generation>> {
+///
+/// `progress(done, total)` is called before each generation is read.
+pub fn list_generations(source: &GenerationSource, progress: &mut dyn FnMut(usize, usize)) -> Result<Vec<Generation>> {
     let profile_path = &source.profile_path;
-    
-    // Get generation list from nix-env
-    let raw_generations = get_raw_generations(profile_path)?;
-    
+
+    // Read the generation links, or ask nix-env if there are none
+    let raw_generations = match read_generation_links(source) {
+        Ok(links) if !links.is_empty() => links,
+        _ => get_raw_generations(profile_path)?,
+    };
+
     // Get current generation ID
     let current_id = get_current_generation_id(profile_path)?;
     
@@ -33,33 +110,91 @@ pub fn list_generations(source: &GenerationSource) -> Result<Vec<Generation>> {
         Vec::new()
     };
 
+    // Metadata of known store paths comes from the cache
+    let cache = MetadataCache::open();
+
     // Parse each generation
     let mut generations = Vec::new();
-    for (id, timestamp) in raw_generations {
-        let gen_path = get_generation_path(profile_path, id, source.profile_type);
+    let mut uncounted = Vec::new(); // (index, package environment) of generations without a count
+    let total = raw_generations.len();
+    for (done, (id, timestamp)) in raw_generations.into_iter().enumerate() {
+        progress(done, total);
+        let gen_path = source.generation_path(id);
         
         if !gen_path.exists() {
             continue; // Skip if path doesn't exist
         }
 
-        let generation = parse_generation(
+        let (generation, counted) = parse_generation(
             id,
             timestamp,
             &gen_path,
             id == current_id,
             boot_entries.contains(&id),
             source.profile_type,
+            cache.as_ref(),
         )?;
-        
+
+        if !counted {
+            if let Some(env) = package_environment(&gen_path, source.profile_type) {
+                uncounted.push((generations.len(), env));
+            }
+        }
         generations.push(generation);
     }
 
+    fill_closure_sizes(&mut generations, cache.as_ref());
+    fill_package_counts(&mut generations, &uncounted, cache.as_ref());
+
     // Sort by ID descending (newest first)
-    generations.sort_by(|a, b| b.id.cmp(&a.id));
+    generations.sort_by_key(|g| std::cmp::Reverse(g.id));
 
     Ok(generations)
 }
 
+/// List generation IDs from the `<profile>-<id>-link` symlinks, newest first
+///
+/// Much cheaper than `list_generations` (no nix-env, no metadata),
+/// which keeps shell completion responsive.
+pub fn list_generation_ids(source: &GenerationSource) -> Result<Vec<u32>> {
+    let mut ids: Vec<u32> = read_generation_links(source)?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+
+    ids.sort_by_key(|&id| std::cmp::Reverse(id));
+    Ok(ids)
+}
+
+/// Read generation IDs and timestamps from the `<profile>-<id>-link` symlinks
+///
+/// The timestamp is the link's own modification time, which is what
+/// `nix-env --list-generations` reports too.
+fn read_generation_links(source: &GenerationSource) -> Result<Vec<(u32, DateTime<Local>)>> {
+    let dir = source.profile_path.parent().unwrap_or(Path::new("/"));
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read profile directory {:?}", dir))?;
+
+    let mut links = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(id) = extract_generation_id(&path) else {
+            continue;
+        };
+        // Only links belonging to this profile
+        if path != source.generation_path(id) {
+            continue;
+        }
+
+        let modified = std::fs::symlink_metadata(&path)
+            .and_then(|meta| meta.modified())
+            .with_context(|| format!("Failed to read link metadata: {:?}", path))?;
+        links.push((id, DateTime::<Local>::from(modified)));
+    }
+
+    Ok(links)
+}
+
 /// Get raw generation list (ID and timestamp) from nix-env
 fn get_raw_generations(profile_path: &Path) -> Result<Vec<(u32, DateTime<Local>)>> {
     let output = Command::new("nix-env")
@@ -118,7 +253,7 @@ fn parse_datetime(s: &str) -> Result<DateTime<Local>> {
     
     Ok(Local.from_local_datetime(&naive)
         .single()
-        .unwrap_or_else(|| Local::now()))
+        .unwrap_or_else(Local::now))
 }
 
 /// Get the current generation ID
@@ -147,16 +282,93 @@ fn extract_generation_id(path: &Path) -> Result<u32> {
 }
 
 /// Get the path to a specific generation
-fn get_generation_path(profile_path: &Path, id: u32, profile_type: ProfileType) -> PathBuf {
+///
+/// Generation links are named after the profile, e.g. `system-142-link`
+/// next to `system`.
+pub fn get_generation_path(profile_path: &Path, id: u32, profile_type: ProfileType) -> PathBuf {
     let parent = profile_path.parent().unwrap_or(Path::new("/"));
-    let name = match profile_type {
-        ProfileType::System => format!("system-{}-link", id),
-        ProfileType::HomeManager => format!("home-manager-{}-link", id),
+    let profile_name = profile_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(match profile_type {
+            ProfileType::System => "system",
+            ProfileType::HomeManager => "home-manager",
+            ProfileType::NixProfile | ProfileType::NixEnv => "profile",
+        });
+    parent.join(format!("{}-{}-link", profile_name, id))
+}
+
+/// Closure sizes of all uncached generations in one `nix path-info` call
+fn fill_closure_sizes(generations: &mut [Generation], cache: Option<&MetadataCache>) {
+    let store_paths: Vec<&str> = generations
+        .iter()
+        .filter(|g| g.closure_size == 0 && !g.store_path.is_empty())
+        .map(|g| g.store_path.as_str())
+        .collect();
+    let closure_sizes = path_sizes(&store_paths, SizeKind::Closure);
+
+    for gen in generations {
+        if let Some(&size) = closure_sizes.get(&gen.store_path) {
+            gen.closure_size = size;
+            if let Some(cache) = cache {
+                cache.update(&gen.store_path, |entry| entry.closure_size = Some(size));
+            }
+        }
+    }
+}
+
+/// Package counts from the references of package environments, in one
+/// `nix path-info` call (counts stay 0 if Nix cannot be queried)
+fn fill_package_counts(
+    generations: &mut [Generation],
+    uncounted: &[(usize, String)],
+    cache: Option<&MetadataCache>,
+) {
+    let envs: Vec<&str> = uncounted.iter().map(|(_, env)| env.as_str()).collect();
+    let references = path_references(&envs);
+
+    for (index, env) in uncounted {
+        let Some(refs) = references.get(env) else {
+            continue;
+        };
+        let gen = &mut generations[*index];
+        let count = count_packages(env, refs);
+        gen.package_count = count;
+        if let Some(cache) = cache {
+            cache.update(&gen.store_path, |entry| entry.package_count = Some(count));
+        }
+    }
+}
+
+/// Store path whose references are the packages installed in a generation
+fn package_environment(gen_path: &Path, profile_type: ProfileType) -> Option<String> {
+    let env = match profile_type {
+        ProfileType::System => gen_path.join("sw"),
+        ProfileType::HomeManager => gen_path.join("home-path"),
+        // The user environment itself
+        ProfileType::NixProfile | ProfileType::NixEnv => gen_path.to_path_buf(),
     };
-    parent.join(name)
+    store_path_of(&env)
+}
+
+/// Number of distinct packages among the references of a package environment
+///
+/// Outputs of one package (`foo-1.0`, `foo-1.0-man`) count once; the
+/// environment itself, derivations and the `nix-env` manifest are skipped.
+fn count_packages(env: &str, references: &[String]) -> usize {
+    references
+        .iter()
+        .filter(|r| r.as_str() != env && !r.ends_with(".drv"))
+        .filter_map(|r| parse_store_path(r).map(|(name, _)| name))
+        .filter(|name| name != "env-manifest.nix")
+        .collect::<HashSet<_>>()
+        .len()
 }
 
 /// Parse a single generation's metadata
+///
+/// Also returns whether the package count is known; unknown counts are
+/// filled in for all generations at once by `fill_package_counts`.
 fn parse_generation(
     id: u32,
     timestamp: DateTime<Local>,
@@ -164,40 +376,60 @@ fn parse_generation(
     is_current: bool,
     in_bootloader: bool,
     profile_type: ProfileType,
-) -> Result<Generation> {
-    // Get NixOS/HM version
-    let nixos_version = get_version(gen_path, profile_type);
-    
-    // Get kernel version (system only)
-    let kernel_version = if profile_type == ProfileType::System {
-        get_kernel_version(gen_path)
-    } else {
-        None
-    };
-
-    // Get package count
-    let package_count = get_package_count(gen_path);
-
-    // Get closure size
-    let closure_size = get_closure_size(gen_path).unwrap_or(0);
-
+    cache: Option<&MetadataCache>,
+) -> Result<(Generation, bool)> {
     // Get store path
     let store_path = std::fs::read_link(gen_path)
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
 
-    Ok(Generation {
+    let cached = cache.and_then(|c| c.get(&store_path)).unwrap_or_default();
+
+    let (nixos_version, kernel_version) = match cached.versions {
+        Some(versions) => versions,
+        None => {
+            // Get NixOS/HM version
+            let nixos_version = get_version(gen_path, profile_type);
+
+            // Get kernel version (system only)
+            let kernel_version = if profile_type == ProfileType::System {
+                get_kernel_version(gen_path)
+            } else {
+                None
+            };
+
+            if let Some(cache) = cache {
+                let versions = (nixos_version.clone(), kernel_version.clone());
+                cache.update(&store_path, |entry| entry.versions = Some(versions));
+            }
+            (nixos_version, kernel_version)
+        }
+    };
+
+    // `nix profile`: one active element per installed package in manifest.json
+    let package_count = cached.package_count.or_else(|| {
+        read_manifest(gen_path).ok().map(|entries| entries.iter().filter(|e| e.active).count())
+    });
+
+    let generation = Generation {
         id,
         date: timestamp,
         is_current,
         nixos_version,
         kernel_version,
-        package_count,
-        closure_size,
+        package_count: package_count.unwrap_or(0), // Unknown counts are filled in later
+        closure_size: cached.closure_size.unwrap_or(0), // Uncached sizes are filled in later
+        closure_count: cached.closure_count,
         store_path,
         is_pinned: false, // Will be set by app based on config
         in_bootloader,
-    })
+        specialisations: if profile_type == ProfileType::System {
+            get_specialisations(gen_path)
+        } else {
+            Vec::new()
+        },
+    };
+    Ok((generation, package_count.is_some()))
 }
 
 /// Get NixOS or Home-Manager version
@@ -205,6 +437,7 @@ fn get_version(gen_path: &Path, profile_type: ProfileType) -> Option<String> {
     let version_file = match profile_type {
         ProfileType::System => gen_path.join("nixos-version"),
         ProfileType::HomeManager => gen_path.join("hm-version"),
+        ProfileType::NixProfile | ProfileType::NixEnv => return None,
     };
 
     if version_file.exists() {
@@ -226,6 +459,20 @@ fn get_version(gen_path: &Path, profile_type: ProfileType) -> Option<String> {
     }
 }
 
+/// Names of the specialisations of a system generation, sorted
+fn get_specialisations(gen_path: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(gen_path.join("specialisation")) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
 /// Get kernel version from a generation
 fn get_kernel_version(gen_path: &Path) -> Option<String> {
     let kernel_dir = gen_path.join("kernel");
@@ -257,54 +504,58 @@ fn get_kernel_version(gen_path: &Path) -> Option<String> {
     }
 }
 
-/// Get the number of packages in a generation
-fn get_package_count(gen_path: &Path) -> usize {
-    let sw_path = gen_path.join("sw/bin");
-    
-    if sw_path.exists() {
-        // Count binaries as a rough estimate
-        std::fs::read_dir(&sw_path)
-            .map(|entries| entries.count())
-            .unwrap_or(0)
-    } else {
-        // For home-manager, count from manifest
-        let manifest = gen_path.join("home-files/.nix-profile/manifest.nix");
-        if manifest.exists() {
-            // This is a rough estimate
-            std::fs::read_to_string(&manifest)
-                .map(|s| s.matches("name = ").count())
-                .unwrap_or(0)
-        } else {
-            0
+/// Number of store paths in the closures of generations (cached per store path)
+///
+/// Uncached closures are read in one batch (see `path_info::closure_counts`);
+/// generations whose closure could not be read are missing from the map.
+pub fn get_closure_counts(gen_paths: &[PathBuf]) -> HashMap<PathBuf, usize> {
+    let cache = MetadataCache::open();
+    let mut counts = HashMap::new();
+    let mut uncached = Vec::new(); // (generation, store path)
+
+    for gen_path in gen_paths {
+        let Some(store_path) = store_path_of(gen_path) else {
+            continue;
+        };
+        match cache.as_ref().and_then(|c| c.get(&store_path)?.closure_count) {
+            Some(count) => {
+                counts.insert(gen_path.clone(), count);
+            }
+            None => uncached.push((gen_path, store_path)),
         }
     }
+
+    let store_paths: Vec<&str> = uncached.iter().map(|(_, store_path)| store_path.as_str()).collect();
+    let read = closure_counts(&store_paths);
+    for (gen_path, store_path) in &uncached {
+        let Some(&count) = read.get(store_path) else {
+            continue;
+        };
+        if let Some(cache) = &cache {
+            cache.update(store_path, |entry| entry.closure_count = Some(count));
+        }
+        counts.insert((*gen_path).clone(), count);
+    }
+    counts
 }
 
-/// Get the closure size of a generation
-fn get_closure_size(gen_path: &Path) -> Result<u64> {
-    let output = Command::new("nix")
-        .args(["path-info", "-S"])
-        .arg(gen_path)
-        .output()
-        .context("Failed to run nix path-info")?;
+/// Closure size of a generation or any other store path (cached per store path)
+pub fn get_closure_size(path: &Path) -> Result<u64> {
+    let store_path = store_path_of(path)
+        .with_context(|| format!("Not a store path: {:?}", path))?;
+    let cache = MetadataCache::open();
 
-    if !output.status.success() {
-        return Ok(0);
+    if let Some(size) = cache.as_ref().and_then(|c| c.get(&store_path)?.closure_size) {
+        return Ok(size);
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    
-    // Output format: /nix/store/xxx-... 1234567890
-    for line in stdout.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            if let Ok(size) = parts[1].parse::<u64>() {
-                return Ok(size);
-            }
-        }
+    let size = path_sizes(&[store_path.as_str()], SizeKind::Closure)
+        .remove(&store_path)
+        .with_context(|| format!("Failed to read the closure size of {}", store_path))?;
+    if let Some(cache) = &cache {
+        cache.update(&store_path, |entry| entry.closure_size = Some(size));
     }
-
-    Ok(0)
+    Ok(size)
 }
 
 /// Get list of generations in the bootloader
@@ -380,4 +631,106 @@ mod tests {
         let path = PathBuf::from("home-manager-89-link");
         assert_eq!(extract_generation_id(&path).unwrap(), 89);
     }
+
+    #[test]
+    fn test_count_packages() {
+        let env = "/nix/store/aaaa-system-path";
+        let references: Vec<String> = [
+            "/nix/store/aaaa-system-path",
+            "/nix/store/bbbb-firefox-122.0",
+            "/nix/store/cccc-git-2.45.1",
+            "/nix/store/dddd-git-2.45.1-man",
+            "/nix/store/eeee-env-manifest.nix",
+            "/nix/store/ffff-ripgrep-14.1.0.drv",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        assert_eq!(count_packages(env, &references), 2);
+    }
+
+    #[test]
+    fn test_list_generation_ids() {
+        let dir = std::env::temp_dir().join(format!("nixhist-ids-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["system", "system-9-link", "system-12-link", "home-manager-3-link", "system-x-link"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let source = GenerationSource {
+            id: ProfileId::SYSTEM,
+            profile_type: ProfileType::System,
+            profile_path: dir.join("system"),
+        };
+        assert_eq!(list_generation_ids(&source).unwrap(), vec![12, 9]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_generations_from_links() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("nixhist-links-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("store")).unwrap();
+        for id in [7, 8] {
+            let target = dir.join(format!("store/home-manager-generation-{}", id));
+            std::fs::create_dir_all(&target).unwrap();
+            symlink(&target, dir.join(format!("home-manager-{}-link", id))).unwrap();
+        }
+        symlink("home-manager-7-link", dir.join("home-manager")).unwrap();
+
+        let source = GenerationSource {
+            id: ProfileType::HomeManager.into(),
+            profile_type: ProfileType::HomeManager,
+            profile_path: dir.join("home-manager"),
+        };
+        let mut steps = Vec::new();
+        let generations = list_generations(&source, &mut |done, total| steps.push((done, total))).unwrap();
+        assert_eq!(steps, [(0, 2), (1, 2)]);
+
+        let summary: Vec<(u32, bool)> = generations.iter().map(|g| (g.id, g.is_current)).collect();
+        assert_eq!(summary, [(8, false), (7, true)]);
+        assert!(generations[0].store_path.ends_with("home-manager-generation-8"));
+        assert!((Local::now() - generations[0].date).num_minutes() < 5);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_nix_profile_counts_active_elements() {
+        use std::os::unix::fs::symlink;
+
+        // Version 2 (list) and version 3 (map) manifests, each with an inactive element
+        let v2 = r#"{"version": 2, "elements": [
+            {"active": true, "storePaths": ["/nix/store/aaaa-ripgrep-14.1.0"]},
+            {"active": false, "storePaths": ["/nix/store/bbbb-hello-2.12.1"]},
+            {"storePaths": ["/nix/store/cccc-jq-1.7.1"]}
+        ]}"#;
+        let v3 = r#"{"version": 3, "elements": {
+            "ripgrep": {"active": true, "storePaths": ["/nix/store/aaaa-ripgrep-14.1.0"]},
+            "neovim": {"active": false, "storePaths": ["/nix/store/dddd-neovim-0.10.0"]}
+        }}"#;
+
+        let dir = std::env::temp_dir().join(format!("nixhist-manifest-test-{}", std::process::id()));
+        for (id, manifest) in [(3, v2), (4, v3)] {
+            let target = dir.join(format!("store/profile-{}", id));
+            std::fs::create_dir_all(&target).unwrap();
+            std::fs::write(target.join("manifest.json"), manifest).unwrap();
+            symlink(&target, dir.join(format!("profile-{}-link", id))).unwrap();
+        }
+        symlink("profile-4-link", dir.join("profile")).unwrap();
+
+        let source = GenerationSource {
+            id: ProfileType::NixProfile.into(),
+            profile_type: ProfileType::NixProfile,
+            profile_path: dir.join("profile"),
+        };
+        let generations = list_generations(&source, &mut |_, _| {}).unwrap();
+        let counts: Vec<(u32, usize)> = generations.iter().map(|g| (g.id, g.package_count)).collect();
+        assert_eq!(counts, [(4, 1), (3, 2)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,194 @@
+//! Package version history across generations
+//!
+//! Walks the generations of a profile from oldest to newest and records
+//! every generation in which a package appeared, changed version or
+//! disappeared. Package lists are cached per generation store path, since
+//! reading one means a `nix path-info` call unless the metadata cache (see
+//! `nix::cache`) already has it; lists loaded for the Packages tab are
+//! added as well, so they are not read again.
+
+use crate::nix::{compare_versions, GenerationSource, NixBackend};
+use crate::types::{Generation, Package};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A generation in which a package's version changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    pub gen_id: u32,
+    pub date: DateTime<Local>,
+    /// None if the package was removed in this generation
+    pub version: Option<String>,
+    /// None if the package was added in this generation
+    pub previous: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Short description of the change
+    pub fn change_label(&self) -> &'static str {
+        match (&self.previous, &self.version) {
+            (None, _) => "added",
+            (_, None) => "removed",
+            (Some(old), Some(new)) => match compare_versions(old, new) {
+                Ordering::Less => "upgraded",
+                Ordering::Greater => "downgraded",
+                Ordering::Equal => "changed",
+            },
+        }
+    }
+
+    /// Version(s) involved in the change, e.g. "121.0 → 122.0"
+    pub fn version_text(&self) -> String {
+        match (&self.previous, &self.version) {
+            (Some(old), Some(new)) => format!("{} → {}", old, new),
+            (None, Some(new)) => new.clone(),
+            (Some(old), None) => old.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// In-memory cache of package lists, keyed by generation store path
+#[derive(Debug, Default)]
+pub struct PackageCache {
+    packages: HashMap<String, Vec<Package>>,
+}
+
+impl PackageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packages of a generation, reading them on first use
+    pub fn packages(
+        &mut self,
+        backend: &dyn NixBackend,
+        source: &GenerationSource,
+        gen: &Generation,
+    ) -> &[Package] {
+        self.packages
+            .entry(Self::key(source, gen))
+            .or_insert_with(|| backend.get_packages(&source.generation_path(gen.id)).unwrap_or_default())
+    }
+
+    /// Remember packages that were read elsewhere
+    pub fn insert(&mut self, source: &GenerationSource, gen: &Generation, packages: Vec<Package>) {
+        self.packages.insert(Self::key(source, gen), packages);
+    }
+
+    /// The store path identifies the generation's content; fall back to
+    /// the link path if it could not be resolved
+    fn key(source: &GenerationSource, gen: &Generation) -> String {
+        if gen.store_path.is_empty() {
+            source.generation_path(gen.id).to_string_lossy().into_owned()
+        } else {
+            gen.store_path.clone()
+        }
+    }
+}
+
+/// Version history of a package over the given generations
+///
+/// Calls `progress(done, total)` before each generation is read and stops
+/// (returning None) as soon as it returns false.
+pub fn package_history(
+    backend: &dyn NixBackend,
+    name: &str,
+    generations: &[Generation],
+    source: &GenerationSource,
+    cache: &mut PackageCache,
+    progress: &mut dyn FnMut(usize, usize) -> bool,
+) -> Option<Vec<HistoryEntry>> {
+    let mut sorted: Vec<&Generation> = generations.iter().collect();
+    sorted.sort_by_key(|g| g.id);
+
+    let mut versions = Vec::with_capacity(sorted.len());
+    for (done, gen) in sorted.iter().enumerate() {
+        if !progress(done, sorted.len()) {
+            return None;
+        }
+        let version = cache
+            .packages(backend, source, gen)
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.version.clone());
+        versions.push((*gen, version));
+    }
+
+    Some(history_from_versions(versions))
+}
+
+/// Collapse per-generation versions (oldest first) into changes
+fn history_from_versions<'a>(
+    versions: impl IntoIterator<Item = (&'a Generation, Option<String>)>,
+) -> Vec<HistoryEntry> {
+    let mut history = Vec::new();
+    let mut previous: Option<String> = None;
+
+    for (gen, version) in versions {
+        if version != previous {
+            history.push(HistoryEntry {
+                gen_id: gen.id,
+                date: gen.date,
+                version: version.clone(),
+                previous: previous.clone(),
+            });
+            previous = version;
+        }
+    }
+
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen(id: u32) -> Generation {
+        Generation {
+            id,
+            date: Local::now(),
+            is_current: false,
+            nixos_version: None,
+            kernel_version: None,
+            package_count: 0,
+            closure_size: 0,
+            closure_count: None,
+            store_path: String::new(),
+            is_pinned: false,
+            in_bootloader: false,
+            specialisations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_history_from_versions() {
+        let gens: Vec<Generation> = (1..=6).map(gen).collect();
+        let versions = [None, Some("121.0"), Some("121.0"), Some("122.0"), None, Some("120.0")];
+
+        let history = history_from_versions(
+            gens.iter().zip(versions.iter().map(|v| v.map(String::from))),
+        );
+
+        let summary: Vec<(u32, &str)> = history.iter().map(|e| (e.gen_id, e.change_label())).collect();
+        assert_eq!(
+            summary,
+            [(2, "added"), (4, "upgraded"), (5, "removed"), (6, "added")]
+        );
+        assert_eq!(history[1].previous.as_deref(), Some("121.0"));
+        assert_eq!(history[1].version.as_deref(), Some("122.0"));
+    }
+
+    #[test]
+    fn test_downgrade_label() {
+        let entry = HistoryEntry {
+            gen_id: 1,
+            date: Local::now(),
+            version: Some("3.9.19".into()),
+            previous: Some("3.11.9".into()),
+        };
+        assert_eq!(entry.change_label(), "downgraded");
+    }
+}
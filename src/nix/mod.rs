@@ -5,13 +5,36 @@
 //! - Generation listing and parsing
 //! - Package extraction
 //! - Command execution (restore, delete)
+//! - Version comparison and store name parsing (Nix semantics)
+//! - Dependency chains (why is a package in a generation)
+//! - Package version history across generations
+//! - Backend trait over all Nix calls (with an in-memory fake for tests)
+//! - `nix profile` manifests (manifest.json)
+//! - Batched store path sizes and references (`nix path-info --json`)
+//! - Full-closure diffs that detect same-version rebuilds
+//! - Persistent metadata cache keyed by store path
 
 pub mod detect;
 pub mod generations;
 pub mod packages;
 pub mod commands;
+pub mod version;
+pub mod drv_name;
+pub mod why;
+pub mod history;
+pub mod backend;
+pub mod profile;
+pub mod path_info;
+pub mod closure;
+pub mod cache;
+#[cfg(test)]
+pub mod fake;
 
 pub use detect::{SystemInfo, detect_system};
-pub use generations::{list_generations, GenerationSource};
-pub use packages::get_packages;
-pub use commands::{restore_generation, delete_generations, CommandResult};
+pub use generations::{list_generation_ids, GenerationSource};
+pub use commands::CommandResult;
+pub use version::compare_versions;
+pub use why::WhyNode;
+pub use history::{package_history, HistoryEntry, PackageCache};
+pub use backend::{NixBackend, SystemBackend};
+pub use profile::read_manifest;
@@ -1,15 +1,40 @@
 //! Package extraction from generations
 //!
 //! Extracts the list of packages installed in a given generation.
+//! Outputs of one derivation (`git-2.45.1`, `git-2.45.1-man`) are grouped
+//! into one package whose size is the sum of its outputs.
 
-use crate::types::Package;
+use crate::nix::cache::{store_path_of, MetadataCache};
+use crate::nix::drv_name::parse_store_name;
+use crate::nix::profile;
+use crate::types::{Package, PackageOutput};
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
 
-/// Get all packages in a generation
+/// Get all packages in a generation (from the metadata cache if possible)
 pub fn get_packages(gen_path: &Path) -> Result<Vec<Package>> {
+    let cache = MetadataCache::open().zip(store_path_of(gen_path));
+    if let Some(packages) = cache.as_ref().and_then(|(c, path)| c.get(path)?.packages) {
+        return Ok(packages);
+    }
+
+    let packages = read_packages(gen_path)?;
+    // An empty list is more likely a failed read than an empty generation
+    if let (Some((cache, path)), false) = (&cache, packages.is_empty()) {
+        cache.update(path, |entry| entry.packages = Some(packages.clone()));
+    }
+    Ok(packages)
+}
+
+/// Read the packages of a generation from the store
+fn read_packages(gen_path: &Path) -> Result<Vec<Package>> {
+    // `nix profile` generations list their elements in manifest.json
+    if gen_path.join("manifest.json").exists() {
+        return profile::get_profile_packages(gen_path);
+    }
+
     // Try nix path-info first (most reliable)
     if let Ok(packages) = get_packages_from_path_info(gen_path) {
         if !packages.is_empty() {
@@ -50,13 +75,12 @@ fn parse_path_info_json(json_str: &str) -> Result<Vec<Package>> {
     let data: HashMap<String, serde_json::Value> = serde_json::from_str(json_str)
         .context("Failed to parse nix path-info JSON")?;
 
-    let mut packages: Vec<Package> = Vec::new();
-    let mut seen_names: HashMap<String, usize> = HashMap::new();
-
+    // Group the outputs of each derivation (name and version)
+    let mut derivations: HashMap<(String, String), Vec<PackageOutput>> = HashMap::new();
     for (path, info) in data {
-        if let Some((name, version)) = parse_store_path(&path) {
+        if let Some(store_name) = parse_store_name(&path) {
             // Skip internal/build-time packages
-            if should_skip_package(&name) {
+            if should_skip_package(&store_name.name) {
                 continue;
             }
 
@@ -64,24 +88,32 @@ fn parse_path_info_json(json_str: &str) -> Result<Vec<Package>> {
                 .and_then(|v| v.as_u64())
                 .unwrap_or(0);
 
-            // Handle duplicate package names (keep the one with larger size)
-            if let Some(&idx) = seen_names.get(&name) {
-                if packages[idx].size < size {
-                    packages[idx] = Package {
-                        name: name.clone(),
-                        version,
-                        size,
-                    };
-                }
-            } else {
-                seen_names.insert(name.clone(), packages.len());
-                packages.push(Package { name, version, size });
+            derivations
+                .entry((store_name.name, store_name.version))
+                .or_default()
+                .push(PackageOutput { name: store_name.output, size });
+        }
+    }
+
+    let mut packages: Vec<Package> = Vec::new();
+    let mut seen_names: HashMap<String, usize> = HashMap::new();
+
+    for ((name, version), outputs) in derivations {
+        let package = package_from_outputs(name, version, outputs);
+
+        // Handle duplicate package names (keep the one with larger size)
+        if let Some(&idx) = seen_names.get(&package.name) {
+            if packages[idx].size < package.size {
+                packages[idx] = package;
             }
+        } else {
+            seen_names.insert(package.name.clone(), packages.len());
+            packages.push(package);
         }
     }
 
     // Sort by name
-    packages.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    packages.sort_by_key(|p| p.name.to_lowercase());
 
     Ok(packages)
 }
@@ -89,36 +121,24 @@ fn parse_path_info_json(json_str: &str) -> Result<Vec<Package>> {
 /// Parse a Nix store path to extract name and version
 /// 
 /// Example: /nix/store/abc123-firefox-122.0 -> ("firefox", "122.0")
-fn parse_store_path(path: &str) -> Option<(String, String)> {
-    // Get the last component
-    let filename = path.rsplit('/').next()?;
-    
-    // Remove the hash prefix (32 chars + dash)
-    if filename.len() <= 33 {
-        return None;
-    }
-    let name_version = &filename[33..];
+///
+/// Output suffixes are dropped (`git-2.45.1-man` is `git` 2.45.1); see
+/// `parse_store_name` for the output.
+pub fn parse_store_path(path: &str) -> Option<(String, String)> {
+    parse_store_name(path).map(|n| (n.name, n.version))
+}
 
-    // Find the last dash that separates name from version
-    // Version typically starts with a digit
-    let mut split_pos = None;
-    let chars: Vec<char> = name_version.chars().collect();
-    
-    for i in (1..chars.len()).rev() {
-        if chars[i-1] == '-' && chars[i].is_ascii_digit() {
-            split_pos = Some(i - 1);
-            break;
-        }
+/// One package from the outputs of a derivation
+///
+/// The output list is only kept for multi-output packages, `out` first.
+pub fn package_from_outputs(name: String, version: String, mut outputs: Vec<PackageOutput>) -> Package {
+    let size = outputs.iter().map(|o| o.size).sum();
+    if outputs.len() < 2 {
+        outputs.clear();
     }
+    outputs.sort_by(|a, b| (a.name != "out", &a.name).cmp(&(b.name != "out", &b.name)));
 
-    if let Some(pos) = split_pos {
-        let name = name_version[..pos].to_string();
-        let version = name_version[pos+1..].to_string();
-        Some((name, version))
-    } else {
-        // No version found, use whole string as name
-        Some((name_version.to_string(), "".to_string()))
-    }
+    Package { name, version, size, attr_path: None, outputs }
 }
 
 /// Check if a package should be skipped (build-time, internal, etc.)
@@ -205,13 +225,15 @@ fn get_packages_from_sw(gen_path: &Path) -> Result<Vec<Package>> {
                                 name,
                                 version,
                                 size: 0, // Unknown when scanning this way
+                                attr_path: None,
+                                outputs: Vec::new(),
                             });
                         }
                     }
                 }
             }
         }
-        packages.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        packages.sort_by_key(|p| p.name.to_lowercase());
         return Ok(packages);
     }
 
@@ -241,13 +263,15 @@ fn parse_manifest(path: &Path) -> Result<Vec<Package>> {
                         name: pkg_name,
                         version,
                         size: 0,
+                        attr_path: None,
+                        outputs: Vec::new(),
                     });
                 }
             }
         }
     }
 
-    packages.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    packages.sort_by_key(|p| p.name.to_lowercase());
     Ok(packages)
 }
 
@@ -273,6 +297,29 @@ mod tests {
         assert_eq!(version, "6.6.52");
     }
 
+    #[test]
+    fn test_parse_path_info_groups_outputs() {
+        let json = r#"{
+            "/nix/store/aaaa-git-2.45.1": {"narSize": 4000},
+            "/nix/store/bbbb-git-2.45.1-man": {"narSize": 1000},
+            "/nix/store/cccc-python3.11-requests-2.31.0": {"narSize": 500},
+            "/nix/store/dddd-x264-0-unstable-2024-01-01-lib": {"narSize": 300}
+        }"#;
+
+        let packages = parse_path_info_json(json).unwrap();
+        let summary: Vec<(&str, &str, u64)> = packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str(), p.size))
+            .collect();
+        assert_eq!(summary, [
+            ("git", "2.45.1", 5000),
+            ("python3.11-requests", "2.31.0", 500),
+            ("x264", "0-unstable-2024-01-01", 300),
+        ]);
+        assert_eq!(packages[0].formatted_outputs(), "out 3.9 KB, man 1000 B");
+        assert!(packages[1].outputs.is_empty());
+    }
+
     #[test]
     fn test_should_skip_package() {
         assert!(should_skip_package("bootstrap-tools"));
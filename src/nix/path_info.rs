@@ -0,0 +1,254 @@
+//! Batched `nix path-info` queries
+//!
+//! Sizes and references of many store paths are read with a single
+//! `nix path-info --json` call (per chunk of paths) rather than one process
+//! per path, so startup stays fast on machines with hundreds of generations.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+/// Paths passed to one `nix path-info` call, to stay well below ARG_MAX
+const CHUNK_SIZE: usize = 500;
+
+/// Size and direct references of a store path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathInfo {
+    pub size: u64,
+    pub references: Vec<String>,
+}
+
+/// Which size `nix path-info` should report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeKind {
+    /// Size of the path itself (`narSize`)
+    Nar,
+    /// Size of the path and everything it references (`closureSize`)
+    Closure,
+}
+
+impl SizeKind {
+    fn json_field(self) -> &'static str {
+        match self {
+            SizeKind::Nar => "narSize",
+            SizeKind::Closure => "closureSize",
+        }
+    }
+}
+
+/// Sizes of store paths, keyed by path
+///
+/// Paths whose size could not be read are missing from the map; a failed
+/// `nix path-info` call only loses the sizes of its own chunk.
+pub fn path_sizes(paths: &[&str], kind: SizeKind) -> HashMap<String, u64> {
+    let args: &[&str] = match kind {
+        SizeKind::Nar => &[],
+        SizeKind::Closure => &["-S"],
+    };
+    query_chunked(paths, args, |info| info.get(kind.json_field())?.as_u64())
+}
+
+/// Direct references of store paths, keyed by path (missing if unreadable)
+pub fn path_references(paths: &[&str]) -> HashMap<String, Vec<String>> {
+    query_chunked(paths, &[], |info| {
+        let references = info.get("references")?.as_array()?;
+        Some(
+            references
+                .iter()
+                .filter_map(|r| r.as_str().map(String::from))
+                .collect(),
+        )
+    })
+}
+
+/// Number of store paths in the closure of each path, keyed by path
+///
+/// One `nix path-info -r` call per chunk reads the closures of all its
+/// paths at once; each count is then taken from the reference graph.
+/// Paths whose closure could not be read are missing from the map.
+pub fn closure_counts(paths: &[&str]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for chunk in paths.chunks(CHUNK_SIZE) {
+        let Ok(json) = run_path_info(chunk, &["-r"]) else {
+            continue;
+        };
+        let graph = parse_entries(&json, parse_path_info);
+        for path in chunk {
+            if let Some(count) = reachable_count(&graph, path) {
+                counts.insert(path.to_string(), count);
+            }
+        }
+    }
+    counts
+}
+
+/// Number of paths reachable from `root`, itself included (None if it is not in the graph)
+fn reachable_count(graph: &HashMap<String, PathInfo>, root: &str) -> Option<usize> {
+    graph.get(root)?;
+    let mut seen = HashSet::from([root]);
+    let mut stack = vec![root];
+    while let Some(path) = stack.pop() {
+        for reference in graph.get(path).map_or(&[][..], |info| &info.references) {
+            if seen.insert(reference) {
+                stack.push(reference);
+            }
+        }
+    }
+    Some(seen.len())
+}
+
+/// Size and references of every store path in the closure of a path
+pub fn closure_info(path: &str) -> Result<HashMap<String, PathInfo>> {
+    let json = run_path_info(&[path], &["-r"])?;
+    Ok(parse_entries(&json, parse_path_info))
+}
+
+/// `narSize` and `references` of one `nix path-info --json` entry
+fn parse_path_info(info: &Value) -> Option<PathInfo> {
+    Some(PathInfo {
+        size: info.get("narSize")?.as_u64()?,
+        references: info
+            .get("references")?
+            .as_array()?
+            .iter()
+            .filter_map(|r| r.as_str().map(String::from))
+            .collect(),
+    })
+}
+
+/// Query paths chunk by chunk and extract one value per path
+fn query_chunked<T>(
+    paths: &[&str],
+    args: &[&str],
+    extract: impl Fn(&Value) -> Option<T> + Copy,
+) -> HashMap<String, T> {
+    paths
+        .chunks(CHUNK_SIZE)
+        .filter_map(|chunk| run_path_info(chunk, args).ok())
+        .flat_map(|json| parse_entries(&json, extract))
+        .collect()
+}
+
+/// Run `nix path-info --json` with extra arguments
+fn run_path_info(paths: &[&str], args: &[&str]) -> Result<String> {
+    let output = Command::new("nix")
+        .args(["path-info", "--json"])
+        .args(args)
+        .args(paths)
+        .output()
+        .context("Failed to run nix path-info")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("nix path-info failed: {}", stderr);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Extract a value per path from `nix path-info --json` output
+/// (object or, before Nix 2.19, list form)
+fn parse_entries<T>(json: &str, extract: impl Fn(&Value) -> Option<T>) -> HashMap<String, T> {
+    let Ok(value) = serde_json::from_str::<Value>(json) else {
+        return HashMap::new();
+    };
+
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .filter(|(_, info)| !info.is_null()) // Invalid paths
+            .filter_map(|(path, info)| Some((path.clone(), extract(info)?)))
+            .collect(),
+        Value::Array(list) => list
+            .iter()
+            .filter_map(|info| {
+                let path = info.get("path")?.as_str()?;
+                Some((path.to_string(), extract(info)?))
+            })
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(json: &str, kind: SizeKind) -> HashMap<String, u64> {
+        parse_entries(json, |info| info.get(kind.json_field())?.as_u64())
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        let object = r#"{"/nix/store/aaaa-ripgrep-14.1.0": {"narSize": 4096}}"#;
+        let list = r#"[{"path": "/nix/store/aaaa-ripgrep-14.1.0", "narSize": 4096}]"#;
+
+        for json in [object, list] {
+            let sizes = sizes(json, SizeKind::Nar);
+            assert_eq!(sizes.get("/nix/store/aaaa-ripgrep-14.1.0"), Some(&4096));
+        }
+    }
+
+    #[test]
+    fn test_parse_closure_sizes() {
+        let json = r#"{
+            "/nix/store/aaaa-nixos-system-host-24.05": {"narSize": 8192, "closureSize": 9000000000},
+            "/nix/store/bbbb-nixos-system-host-24.05": {"narSize": 8192, "closureSize": 9100000000},
+            "/nix/store/cccc-invalid": null
+        }"#;
+
+        let sizes = sizes(json, SizeKind::Closure);
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes.get("/nix/store/aaaa-nixos-system-host-24.05"), Some(&9_000_000_000));
+        assert_eq!(sizes.get("/nix/store/bbbb-nixos-system-host-24.05"), Some(&9_100_000_000));
+    }
+
+    #[test]
+    fn test_parse_references() {
+        let json = r#"{"/nix/store/aaaa-home-manager-path": {
+            "references": ["/nix/store/bbbb-git-2.45.1", "/nix/store/cccc-ripgrep-14.1.0"]
+        }}"#;
+
+        let references = parse_entries(json, |info| {
+            let refs = info.get("references")?.as_array()?;
+            Some(refs.iter().filter_map(|r| r.as_str()).count())
+        });
+        assert_eq!(references.get("/nix/store/aaaa-home-manager-path"), Some(&2));
+    }
+
+    #[test]
+    fn test_parse_path_info() {
+        let json = r#"{
+            "/nix/store/aaaa-firefox-122.0": {"narSize": 4096, "references": ["/nix/store/bbbb-openssl-3.0.14"]},
+            "/nix/store/bbbb-openssl-3.0.14": {"narSize": 1024, "references": []}
+        }"#;
+
+        let infos = parse_entries(json, parse_path_info);
+        assert_eq!(infos["/nix/store/aaaa-firefox-122.0"].size, 4096);
+        assert_eq!(infos["/nix/store/aaaa-firefox-122.0"].references, ["/nix/store/bbbb-openssl-3.0.14"]);
+        assert!(infos["/nix/store/bbbb-openssl-3.0.14"].references.is_empty());
+
+        // List form (before Nix 2.19)
+        let json = r#"[{"path": "/nix/store/aaaa-foo-1.0", "narSize": 1, "references": ["/nix/store/bbbb-bar-2.0"]}]"#;
+        let infos = parse_entries(json, parse_path_info);
+        assert_eq!(infos["/nix/store/aaaa-foo-1.0"].references, ["/nix/store/bbbb-bar-2.0"]);
+    }
+
+    #[test]
+    fn test_reachable_count() {
+        // Two generations sharing firefox, one with an extra package
+        let json = r#"{
+            "/nix/store/1111-system": {"narSize": 1, "references": ["/nix/store/aaaa-firefox-122.0"]},
+            "/nix/store/2222-system": {"narSize": 1, "references": ["/nix/store/aaaa-firefox-122.0", "/nix/store/cccc-git-2.44.0", "/nix/store/2222-system"]},
+            "/nix/store/aaaa-firefox-122.0": {"narSize": 1, "references": ["/nix/store/bbbb-openssl-3.0.14"]},
+            "/nix/store/bbbb-openssl-3.0.14": {"narSize": 1, "references": []},
+            "/nix/store/cccc-git-2.44.0": {"narSize": 1, "references": ["/nix/store/bbbb-openssl-3.0.14"]}
+        }"#;
+
+        let graph = parse_entries(json, parse_path_info);
+        assert_eq!(reachable_count(&graph, "/nix/store/1111-system"), Some(3));
+        assert_eq!(reachable_count(&graph, "/nix/store/2222-system"), Some(4));
+        assert_eq!(reachable_count(&graph, "/nix/store/3333-system"), None);
+    }
+}
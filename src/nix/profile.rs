@@ -0,0 +1,230 @@
+//! `nix profile` manifests
+//!
+//! Generations of profiles managed by `nix profile install` carry a
+//! `manifest.json` listing the installed elements. Version 1 and 2
+//! manifests store the elements as a list, version 3 as an object keyed
+//! by element name.
+
+use crate::nix::drv_name::parse_store_name;
+use crate::nix::packages::{package_from_outputs, parse_store_path};
+use crate::nix::path_info::{path_sizes, SizeKind};
+use crate::types::{Package, PackageOutput};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Newest manifest version understood
+const MAX_MANIFEST_VERSION: u32 = 3;
+
+/// An element installed into a `nix profile`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProfileEntry {
+    pub name: String,
+    /// Flake output attribute, e.g. `legacyPackages.x86_64-linux.ripgrep`
+    pub attr_path: Option<String>,
+    /// Flake reference as given on install, e.g. `flake:nixpkgs`
+    pub original_url: Option<String>,
+    /// Locked flake reference
+    pub url: Option<String>,
+    pub store_paths: Vec<String>,
+    pub active: bool,
+    pub priority: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawManifest {
+    version: u32,
+    elements: RawElements,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawElements {
+    /// Version 1 and 2
+    List(Vec<RawElement>),
+    /// Version 3
+    Map(BTreeMap<String, RawElement>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawElement {
+    #[serde(default = "default_active")]
+    active: bool,
+    attr_path: Option<String>,
+    #[serde(alias = "originalUri")]
+    original_url: Option<String>,
+    #[serde(alias = "uri")]
+    url: Option<String>,
+    #[serde(default)]
+    store_paths: Vec<String>,
+    priority: Option<i64>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Read the elements of a `nix profile` generation
+pub fn read_manifest(gen_path: &Path) -> Result<Vec<ProfileEntry>> {
+    let path = gen_path.join("manifest.json");
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    parse_manifest(&content)
+}
+
+/// Parse a `manifest.json` (version 1 to 3)
+fn parse_manifest(json: &str) -> Result<Vec<ProfileEntry>> {
+    let manifest: RawManifest = serde_json::from_str(json)
+        .context("Failed to parse profile manifest")?;
+
+    if manifest.version > MAX_MANIFEST_VERSION {
+        anyhow::bail!("Unsupported profile manifest version {}", manifest.version);
+    }
+
+    let named: Vec<(String, RawElement)> = match manifest.elements {
+        RawElements::Map(elements) => elements.into_iter().collect(),
+        RawElements::List(elements) => elements
+            .into_iter()
+            .map(|element| (element_name(&element), element))
+            .collect(),
+    };
+
+    Ok(named
+        .into_iter()
+        .map(|(name, element)| ProfileEntry {
+            name,
+            attr_path: element.attr_path,
+            original_url: element.original_url,
+            url: element.url,
+            store_paths: element.store_paths,
+            active: element.active,
+            priority: element.priority,
+        })
+        .collect())
+}
+
+/// Name of a list element, as `nix profile` derives it when upgrading to version 3
+///
+/// The last attribute of the flake output, or the store path name for
+/// elements installed from a store path (and for `default` outputs).
+fn element_name(element: &RawElement) -> String {
+    let from_attr = element
+        .attr_path
+        .as_deref()
+        .and_then(|attr| attr.rsplit('.').next())
+        .filter(|name| !name.is_empty() && *name != "default");
+
+    from_attr
+        .map(String::from)
+        .or_else(|| {
+            let path = element.store_paths.first()?;
+            parse_store_path(path).map(|(name, _)| name)
+        })
+        .unwrap_or_else(|| "unknown".into())
+}
+
+/// Active elements of a `nix profile` generation as packages
+pub fn get_profile_packages(gen_path: &Path) -> Result<Vec<Package>> {
+    let entries = read_manifest(gen_path)?;
+
+    let all_paths: Vec<&str> = entries
+        .iter()
+        .flat_map(|e| e.store_paths.iter().map(String::as_str))
+        .collect();
+    let sizes = path_sizes(&all_paths, SizeKind::Nar);
+
+    let mut packages: Vec<Package> = entries
+        .into_iter()
+        .filter(|e| e.active)
+        .map(|e| {
+            let version = e
+                .store_paths
+                .first()
+                .and_then(|p| parse_store_path(p))
+                .map(|(_, version)| version)
+                .unwrap_or_default();
+            let outputs = e
+                .store_paths
+                .iter()
+                .filter_map(|p| {
+                    let output = parse_store_name(p)?.output;
+                    Some(PackageOutput { name: output, size: sizes.get(p).copied().unwrap_or(0) })
+                })
+                .collect();
+
+            Package {
+                attr_path: e.attr_path,
+                ..package_from_outputs(e.name, version, outputs)
+            }
+        })
+        .collect();
+
+    packages.sort_by_key(|p| p.name.to_lowercase());
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest_v2() {
+        let json = r#"{
+            "version": 2,
+            "elements": [
+                {
+                    "active": true,
+                    "attrPath": "legacyPackages.x86_64-linux.ripgrep",
+                    "originalUrl": "flake:nixpkgs",
+                    "outputs": null,
+                    "priority": 5,
+                    "storePaths": ["/nix/store/aaaa-ripgrep-14.1.0"],
+                    "url": "github:NixOS/nixpkgs/0123abcd"
+                },
+                {
+                    "active": true,
+                    "priority": 5,
+                    "storePaths": ["/nix/store/bbbb-hello-2.12.1"]
+                }
+            ]
+        }"#;
+
+        let entries = parse_manifest(json).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "ripgrep");
+        assert_eq!(entries[0].attr_path.as_deref(), Some("legacyPackages.x86_64-linux.ripgrep"));
+        assert_eq!(entries[0].original_url.as_deref(), Some("flake:nixpkgs"));
+        assert_eq!(entries[1].name, "hello");
+        assert_eq!(entries[1].attr_path, None);
+    }
+
+    #[test]
+    fn test_parse_manifest_v3() {
+        let json = r#"{
+            "version": 3,
+            "elements": {
+                "neovim": {
+                    "active": false,
+                    "attrPath": "packages.x86_64-linux.default",
+                    "originalUrl": "github:neovim/neovim",
+                    "priority": 5,
+                    "storePaths": ["/nix/store/cccc-neovim-0.10.0"],
+                    "url": "github:neovim/neovim/4567ef"
+                }
+            }
+        }"#;
+
+        let entries = parse_manifest(json).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "neovim");
+        assert!(!entries[0].active);
+        assert_eq!(entries[0].url.as_deref(), Some("github:neovim/neovim/4567ef"));
+    }
+
+    #[test]
+    fn test_unsupported_manifest_version() {
+        assert!(parse_manifest(r#"{"version": 4, "elements": {}}"#).is_err());
+    }
+}
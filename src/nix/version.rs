@@ -0,0 +1,237 @@
+//! Version comparison
+//!
+//! Implements the ordering used by `nix-env` and `builtins.compareVersions`,
+//! so sorting and update detection agree with Nix itself. Updates are
+//! classified on the same components.
+
+use crate::types::UpdateKind;
+use std::cmp::Ordering;
+
+/// Components marking a pre-release version
+const PRE_RELEASE: &[&str] = &["pre", "rc", "alpha", "beta"];
+
+/// Compare two version strings the way `builtins.compareVersions` does
+///
+/// Versions are split into components at `.` and `-`, and between
+/// runs of digits and non-digits. Numeric components compare as
+/// numbers, `pre` sorts before everything else, and a missing or
+/// alphabetic component sorts before a numeric one (`2.3a < 2.3.1`).
+pub fn compare_versions(v1: &str, v2: &str) -> Ordering {
+    let mut rest1 = v1;
+    let mut rest2 = v2;
+
+    while !rest1.is_empty() || !rest2.is_empty() {
+        let c1 = next_component(&mut rest1);
+        let c2 = next_component(&mut rest2);
+
+        if component_less(c1, c2) {
+            return Ordering::Less;
+        }
+        if component_less(c2, c1) {
+            return Ordering::Greater;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Classify the change from one version to another
+///
+/// Downgrades win over everything else; otherwise unstable snapshots and
+/// pre-releases are their own kinds, and the position of the first
+/// differing component decides between major, minor and patch.
+///
+/// Unlike `compare_versions`, every pre-release marker sorts before the
+/// release here, so `1.0beta2 → 1.0` is an update rather than a downgrade.
+pub fn classify_update(old: &str, new: &str) -> UpdateKind {
+    if is_downgrade(old, new) {
+        return UpdateKind::Downgrade;
+    }
+    if is_unstable(old) || is_unstable(new) {
+        return UpdateKind::UnstableDate;
+    }
+
+    let old = components(old);
+    let new = components(new);
+    if [&old, &new].iter().any(|c| c.iter().any(|c| PRE_RELEASE.contains(c))) {
+        return UpdateKind::PreRelease;
+    }
+
+    let first_change = (0..old.len().max(new.len())).find(|&i| {
+        let c1 = old.get(i).copied().unwrap_or("");
+        let c2 = new.get(i).copied().unwrap_or("");
+        component_less(c1, c2) || component_less(c2, c1)
+    });
+    match first_change {
+        Some(0) => UpdateKind::Major,
+        Some(1) => UpdateKind::Minor,
+        _ => UpdateKind::Patch,
+    }
+}
+
+/// All components of a version
+fn components(version: &str) -> Vec<&str> {
+    let mut rest = version;
+    let mut components = Vec::new();
+    while !rest.is_empty() {
+        let component = next_component(&mut rest);
+        if !component.is_empty() {
+            components.push(component);
+        }
+    }
+    components
+}
+
+/// Whether `new` sorts before `old`, with pre-releases before their release
+fn is_downgrade(old: &str, new: &str) -> bool {
+    let (mut rest1, mut rest2) = (old, new);
+    while !rest1.is_empty() || !rest2.is_empty() {
+        let c1 = next_component(&mut rest1);
+        let c2 = next_component(&mut rest2);
+        match (PRE_RELEASE.contains(&c1), PRE_RELEASE.contains(&c2)) {
+            (true, false) => return false,
+            (false, true) => return true,
+            _ if component_less(c2, c1) => return true,
+            _ if component_less(c1, c2) => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// `unstable` snapshots and versions with a full date (`2024-05-01`,
+/// `20240501`, as in `2.19pre20240501_abcdef`)
+///
+/// Calendar versions without a day (`2024.10.1`) are ordinary releases.
+fn is_unstable(version: &str) -> bool {
+    if components(version).contains(&"unstable") {
+        return true;
+    }
+
+    let dashed: Vec<&str> = version.split('-').collect();
+    let has_dashed_date = dashed.windows(3).any(|date| {
+        date.iter().all(|c| parse_number(c).is_some()) && is_date(date[0], date[1], date[2])
+    });
+    let has_compact_date = version
+        .split(|c: char| !c.is_ascii_digit())
+        .any(|digits| digits.len() == 8 && is_date(&digits[..4], &digits[4..6], &digits[6..]));
+    has_dashed_date || has_compact_date
+}
+
+/// Whether all-digit year, month and day components form a date
+fn is_date(year: &str, month: &str, day: &str) -> bool {
+    let in_range = |c: &str, len, range: std::ops::RangeInclusive<u64>| {
+        c.len() == len && parse_number(c).is_some_and(|n| range.contains(&n))
+    };
+    in_range(year, 4, 1970..=2099) && in_range(month, 2, 1..=12) && in_range(day, 2, 1..=31)
+}
+
+/// Split the next version component off the front of `rest`
+fn next_component<'a>(rest: &mut &'a str) -> &'a str {
+    // Skip separators
+    *rest = rest.trim_start_matches(['.', '-']);
+
+    let is_digit = match rest.chars().next() {
+        Some(c) => c.is_ascii_digit(),
+        None => return "",
+    };
+
+    let end = rest
+        .find(|c: char| {
+            if is_digit {
+                !c.is_ascii_digit()
+            } else {
+                c.is_ascii_digit() || c == '.' || c == '-'
+            }
+        })
+        .unwrap_or(rest.len());
+
+    let (component, remainder) = rest.split_at(end);
+    *rest = remainder;
+    component
+}
+
+/// Nix's `componentsLT`
+fn component_less(c1: &str, c2: &str) -> bool {
+    let n1 = parse_number(c1);
+    let n2 = parse_number(c2);
+
+    match (n1, n2) {
+        (Some(a), Some(b)) => a < b,
+        _ if c1.is_empty() && n2.is_some() => true,
+        _ if c1 == "pre" && c2 != "pre" => true,
+        _ if c2 == "pre" => false,
+        (_, Some(_)) => true,
+        (Some(_), _) => false,
+        _ => c1 < c2,
+    }
+}
+
+/// Parse an all-digit component
+fn parse_number(component: &str) -> Option<u64> {
+    if !component.is_empty() && component.bytes().all(|b| b.is_ascii_digit()) {
+        component.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        // Examples from the Nix manual
+        assert_eq!(compare_versions("1.0", "2.3"), Ordering::Less);
+        assert_eq!(compare_versions("2.1", "2.3"), Ordering::Less);
+        assert_eq!(compare_versions("2.3", "2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("2.5", "2.3"), Ordering::Greater);
+        assert_eq!(compare_versions("3.1", "2.3"), Ordering::Greater);
+        assert_eq!(compare_versions("2.3.1", "2.3"), Ordering::Greater);
+        assert_eq!(compare_versions("2.3.1", "2.3a"), Ordering::Greater);
+        assert_eq!(compare_versions("2.3pre1", "2.3"), Ordering::Less);
+        assert_eq!(compare_versions("2.3pre3", "2.3pre12"), Ordering::Less);
+        assert_eq!(compare_versions("2.3a", "2.3c"), Ordering::Less);
+        assert_eq!(compare_versions("2.3pre1", "2.3c"), Ordering::Less);
+        assert_eq!(compare_versions("2.3pre1", "2.3q"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_compare_numeric_not_lexical() {
+        assert_eq!(compare_versions("122.0", "99.0"), Ordering::Greater);
+        assert_eq!(compare_versions("6.6.9", "6.6.52"), Ordering::Less);
+        assert_eq!(compare_versions("0-unstable-2024-05-01", "0-unstable-2024-11-30"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_classify_update() {
+        assert_eq!(classify_update("121.0", "122.0"), UpdateKind::Major);
+        assert_eq!(classify_update("2.44.0", "2.45.1"), UpdateKind::Minor);
+        assert_eq!(classify_update("6.6.51", "6.6.52"), UpdateKind::Patch);
+        assert_eq!(classify_update("3.0", "3.0.1"), UpdateKind::Patch);
+        assert_eq!(classify_update("2.3a", "2.3c"), UpdateKind::Patch);
+        assert_eq!(classify_update("6.6.52", "6.6.51"), UpdateKind::Downgrade);
+        assert_eq!(classify_update("2.3", "2.3pre1"), UpdateKind::Downgrade);
+        assert_eq!(classify_update("1.0-rc1", "1.0-rc2"), UpdateKind::PreRelease);
+        assert_eq!(classify_update("1.0pre2", "1.0"), UpdateKind::PreRelease);
+        // Any pre-release marker leads up to the release (Nix only sorts `pre` first)
+        assert_eq!(classify_update("1.0beta2", "1.0"), UpdateKind::PreRelease);
+        assert_eq!(classify_update("1.0", "1.0-rc1"), UpdateKind::Downgrade);
+        assert_eq!(classify_update("1.0-rc2", "1.0-beta3"), UpdateKind::Downgrade);
+        assert_eq!(classify_update("0-unstable-2024-05-01", "0-unstable-2024-11-30"), UpdateKind::UnstableDate);
+        assert_eq!(classify_update("2024-05-01", "2024-11-30"), UpdateKind::UnstableDate);
+        assert_eq!(classify_update("20240501", "20241130"), UpdateKind::UnstableDate);
+        assert_eq!(classify_update("2.19pre20240101_1a2b3c", "2.19pre20240301_4d5e6f"), UpdateKind::UnstableDate);
+        assert_eq!(classify_update("2023.1", "2024.1"), UpdateKind::Major);
+    }
+
+    #[test]
+    fn test_classify_calendar_versions() {
+        // Stable calendar-versioned releases are not unstable snapshots
+        assert_eq!(classify_update("2024.10.1", "2024.11.0"), UpdateKind::Minor);
+        assert_eq!(classify_update("2024.11.0", "2024.11.2"), UpdateKind::Patch);
+        assert_eq!(classify_update("2024.11.0", "2025.1.0"), UpdateKind::Major);
+        assert_eq!(classify_update("2024.11.0", "2024.10.1"), UpdateKind::Downgrade);
+    }
+}
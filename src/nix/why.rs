@@ -0,0 +1,237 @@
+//! Dependency chains ("why is this package here?")
+//!
+//! Builds the reference graph of a generation's closure (read with
+//! `path_info::closure_info`) and extracts the shortest chains from the
+//! generation to one or more packages, like `nix why-depends --all`.
+
+use crate::nix::cache::store_path_of;
+use crate::nix::packages::parse_store_path;
+use crate::nix::path_info::{closure_info, PathInfo};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+/// Reference graph of a closure, keyed by store path base name
+type Graph = HashMap<String, Vec<String>>;
+
+/// A store path in a dependency tree
+#[derive(Debug, Clone, Serialize)]
+pub struct WhyNode {
+    /// Full store path
+    pub path: String,
+    /// Store path name without the hash (e.g. "firefox-122.0")
+    pub name: String,
+    /// One of the packages that was asked about
+    pub is_target: bool,
+    /// Already shown (with its children) elsewhere in the tree
+    pub repeated: bool,
+    pub children: Vec<WhyNode>,
+}
+
+/// A visible line of a (partly collapsed) tree
+#[derive(Debug, Clone)]
+pub struct WhyRow<'a> {
+    /// Pre-order index in the full tree, stable across collapsing
+    pub index: usize,
+    pub depth: usize,
+    pub node: &'a WhyNode,
+}
+
+impl WhyNode {
+    /// Rows in display order, skipping the descendants of collapsed nodes
+    pub fn visible_rows(&self, collapsed: &HashSet<usize>) -> Vec<WhyRow<'_>> {
+        let mut rows = Vec::new();
+        let mut index = 0;
+        self.collect_rows(0, collapsed, true, &mut index, &mut rows);
+        rows
+    }
+
+    fn collect_rows<'a>(
+        &'a self,
+        depth: usize,
+        collapsed: &HashSet<usize>,
+        visible: bool,
+        index: &mut usize,
+        rows: &mut Vec<WhyRow<'a>>,
+    ) {
+        let own_index = *index;
+        *index += 1;
+        if visible {
+            rows.push(WhyRow { index: own_index, depth, node: self });
+        }
+
+        let children_visible = visible && !collapsed.contains(&own_index);
+        for child in &self.children {
+            child.collect_rows(depth + 1, collapsed, children_visible, index, rows);
+        }
+    }
+}
+
+/// Explain why packages are in a generation's closure
+///
+/// Returns None if none of the packages is in the closure.
+pub fn why_depends(gen_path: &Path, packages: &[&str]) -> Result<Option<WhyNode>> {
+    let root = store_path_of(gen_path)
+        .with_context(|| format!("Not a store path: {:?}", gen_path))?;
+
+    let graph = reference_graph(&closure_info(&root)?);
+    Ok(build_tree(&graph, base_name(&root), packages))
+}
+
+/// Reference graph of a closure read by `closure_info`
+fn reference_graph(closure: &HashMap<String, PathInfo>) -> Graph {
+    closure
+        .iter()
+        .map(|(path, info)| {
+            let references = info.references.iter().map(|r| base_name(r).to_string()).collect();
+            (base_name(path).to_string(), references)
+        })
+        .collect()
+}
+
+/// Build the tree of shortest chains from `root` to the named packages
+fn build_tree(graph: &Graph, root: &str, packages: &[&str]) -> Option<WhyNode> {
+    let targets: HashSet<&str> = graph
+        .keys()
+        .filter(|path| {
+            parse_store_path(path).is_some_and(|(name, _)| packages.contains(&name.as_str()))
+        })
+        .map(String::as_str)
+        .collect();
+
+    // Distance of every path from the root
+    let mut depth: HashMap<&str, usize> = HashMap::from([(root, 0)]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(path) = queue.pop_front() {
+        for reference in graph.get(path).into_iter().flatten() {
+            if !depth.contains_key(reference.as_str()) {
+                depth.insert(reference, depth[path] + 1);
+                queue.push_back(reference);
+            }
+        }
+    }
+
+    // Paths on a shortest chain to a target, walking back from the targets
+    let mut relevant: HashSet<&str> = targets.iter().copied().filter(|t| depth.contains_key(t)).collect();
+    if relevant.is_empty() {
+        return None;
+    }
+    let mut by_depth: Vec<&str> = depth.keys().copied().collect();
+    by_depth.sort_by_key(|path| std::cmp::Reverse(depth[path]));
+    for path in by_depth {
+        let leads_to_target = graph.get(path).into_iter().flatten().any(|r| {
+            relevant.contains(r.as_str()) && depth.get(r.as_str()) == Some(&(depth[path] + 1))
+        });
+        if leads_to_target {
+            relevant.insert(path);
+        }
+    }
+
+    let mut expanded = HashSet::new();
+    Some(expand(graph, root, &depth, &relevant, &targets, &mut expanded))
+}
+
+/// Recursively build a node, expanding each path only once
+fn expand<'a>(
+    graph: &'a Graph,
+    path: &'a str,
+    depth: &HashMap<&str, usize>,
+    relevant: &HashSet<&str>,
+    targets: &HashSet<&str>,
+    expanded: &mut HashSet<&'a str>,
+) -> WhyNode {
+    let mut next: Vec<&str> = graph
+        .get(path)
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .filter(|r| relevant.contains(r) && depth.get(r) == Some(&(depth[path] + 1)))
+        .collect();
+    next.sort_by_key(|r| store_name(r));
+    next.dedup();
+
+    let repeated = !expanded.insert(path) && !next.is_empty();
+    let children = if repeated {
+        Vec::new()
+    } else {
+        next.into_iter()
+            .map(|reference| expand(graph, reference, depth, relevant, targets, expanded))
+            .collect()
+    };
+
+    WhyNode {
+        path: format!("/nix/store/{}", path),
+        name: store_name(path).to_string(),
+        is_target: targets.contains(path),
+        repeated,
+        children,
+    }
+}
+
+/// Last component of a store path
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Store path name without the hash
+fn store_name(base: &str) -> &str {
+    base.split_once('-').map(|(_, name)| name).unwrap_or(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// system -> system-path -> {firefox, python3}, python3 -> openssl,
+    /// firefox -> openssl, system -> etc -> openssl
+    fn graph() -> Graph {
+        let closure: HashMap<String, PathInfo> = [
+            ("aaa-nixos-system-host-24.05", &["bbb-system-path", "ccc-etc"][..]),
+            ("bbb-system-path", &["ddd-firefox-122.0", "eee-python3-3.11.9"]),
+            ("ccc-etc", &["fff-openssl-3.0.13"]),
+            ("ddd-firefox-122.0", &["fff-openssl-3.0.13", "ddd-firefox-122.0"]),
+            ("eee-python3-3.11.9", &["fff-openssl-3.0.13"]),
+            ("fff-openssl-3.0.13", &[]),
+        ]
+        .into_iter()
+        .map(|(path, references)| {
+            let references = references.iter().map(|r| format!("/nix/store/{}", r)).collect();
+            (format!("/nix/store/{}", path), PathInfo { size: 0, references })
+        })
+        .collect();
+        reference_graph(&closure)
+    }
+
+    #[test]
+    fn test_reference_graph() {
+        let graph = graph();
+        assert_eq!(graph["aaa-nixos-system-host-24.05"], ["bbb-system-path", "ccc-etc"]);
+        assert!(graph["fff-openssl-3.0.13"].is_empty());
+    }
+
+    #[test]
+    fn test_shortest_chain() {
+        let tree = build_tree(&graph(), "aaa-nixos-system-host-24.05", &["openssl"]).unwrap();
+        // The only shortest chain goes through etc
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].name, "etc");
+        assert_eq!(tree.children[0].children[0].name, "openssl-3.0.13");
+        assert!(tree.children[0].children[0].is_target);
+
+        assert!(build_tree(&graph(), "aaa-nixos-system-host-24.05", &["vim"]).is_none());
+    }
+
+    #[test]
+    fn test_multiple_targets_and_collapse() {
+        let tree = build_tree(&graph(), "aaa-nixos-system-host-24.05", &["firefox", "python3"]).unwrap();
+        let rows = tree.visible_rows(&HashSet::new());
+        let names: Vec<&str> = rows.iter().map(|r| r.node.name.as_str()).collect();
+        assert_eq!(names, ["nixos-system-host-24.05", "system-path", "firefox-122.0", "python3-3.11.9"]);
+        assert_eq!(rows[3].depth, 2);
+
+        // Collapsing system-path hides both packages
+        let rows = tree.visible_rows(&HashSet::from([1]));
+        assert_eq!(rows.len(), 2);
+    }
+}
@@ -0,0 +1,211 @@
+//! Retention policy evaluation
+//!
+//! Decides which generations a `RetentionPolicy` keeps and which it
+//! would delete. Used by `nixhist gc` and to mark deletion candidates
+//! in the Manage tab.
+
+use crate::config::RetentionPolicy;
+use crate::types::Generation;
+use chrono::{DateTime, Datelike, Duration, Local};
+use std::collections::HashSet;
+
+/// Why a generation is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepReason {
+    /// No deletion rules are configured
+    NoRules,
+    Current,
+    Pinned,
+    Bootloader,
+    Last,
+    Recent,
+    Weekly,
+    Monthly,
+}
+
+impl KeepReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeepReason::NoRules => "no rules",
+            KeepReason::Current => "current",
+            KeepReason::Pinned => "pinned",
+            KeepReason::Bootloader => "bootloader",
+            KeepReason::Last => "last",
+            KeepReason::Recent => "recent",
+            KeepReason::Weekly => "weekly",
+            KeepReason::Monthly => "monthly",
+        }
+    }
+}
+
+/// Policy verdict for a single generation
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub id: u32,
+    /// Empty if the generation would be deleted
+    pub reasons: Vec<KeepReason>,
+}
+
+impl Decision {
+    pub fn is_kept(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+/// Result of evaluating a policy, newest generation first
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPlan {
+    pub decisions: Vec<Decision>,
+}
+
+impl RetentionPlan {
+    /// IDs of the generations the policy would delete
+    pub fn to_delete(&self) -> Vec<u32> {
+        self.decisions
+            .iter()
+            .filter(|d| !d.is_kept())
+            .map(|d| d.id)
+            .collect()
+    }
+}
+
+/// Evaluate a retention policy against a profile's generations
+pub fn evaluate(
+    policy: &RetentionPolicy,
+    generations: &[Generation],
+    now: DateTime<Local>,
+) -> RetentionPlan {
+    let mut sorted: Vec<&Generation> = generations.iter().collect();
+    sorted.sort_by_key(|g| std::cmp::Reverse(g.id));
+
+    let mut seen_weeks = HashSet::new();
+    let mut seen_months = HashSet::new();
+
+    let decisions = sorted
+        .iter()
+        .enumerate()
+        .map(|(index, gen)| {
+            let mut reasons = Vec::new();
+
+            if !policy.is_enabled() {
+                reasons.push(KeepReason::NoRules);
+            }
+            if gen.is_current {
+                reasons.push(KeepReason::Current);
+            }
+            if policy.keep_pinned && gen.is_pinned {
+                reasons.push(KeepReason::Pinned);
+            }
+            if policy.keep_bootloader && gen.in_bootloader {
+                reasons.push(KeepReason::Bootloader);
+            }
+            if policy.keep_last.is_some_and(|n| index < n) {
+                reasons.push(KeepReason::Last);
+            }
+            if let Some(days) = policy.keep_days {
+                if gen.date > now - Duration::days(i64::from(days)) {
+                    reasons.push(KeepReason::Recent);
+                }
+            }
+
+            // Newest generation of each of the last N weeks/months
+            if let Some(n) = policy.keep_weekly {
+                let week = gen.date.iso_week();
+                let week = (week.year(), week.week());
+                if seen_weeks.len() < n && seen_weeks.insert(week) {
+                    reasons.push(KeepReason::Weekly);
+                }
+            }
+            if let Some(n) = policy.keep_monthly {
+                let month = (gen.date.year(), gen.date.month());
+                if seen_months.len() < n && seen_months.insert(month) {
+                    reasons.push(KeepReason::Monthly);
+                }
+            }
+
+            Decision { id: gen.id, reasons }
+        })
+        .collect();
+
+    RetentionPlan { decisions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Generation `id` created `days_ago` days before `now()`
+    fn gen(id: u32, days_ago: i64) -> Generation {
+        Generation {
+            id,
+            date: now() - Duration::days(days_ago),
+            is_current: false,
+            nixos_version: None,
+            kernel_version: None,
+            package_count: 0,
+            closure_size: 0,
+            closure_count: None,
+            store_path: String::new(),
+            is_pinned: false,
+            in_bootloader: false,
+            specialisations: Vec::new(),
+        }
+    }
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap()
+    }
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            keep_pinned: false,
+            keep_bootloader: false,
+            ..RetentionPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_policy_keeps_everything() {
+        let gens = vec![gen(3, 0), gen(2, 100), gen(1, 200)];
+        let plan = evaluate(&RetentionPolicy::default(), &gens, now());
+        assert!(plan.to_delete().is_empty());
+    }
+
+    #[test]
+    fn test_keep_last_and_protected() {
+        let mut gens: Vec<Generation> = (1..=6).map(|id| gen(id, 60 - id as i64)).collect();
+        gens[0].is_pinned = true; // id 1
+        gens[1].in_bootloader = true; // id 2
+        gens[3].is_current = true; // id 4
+
+        let rules = RetentionPolicy { keep_last: Some(2), ..RetentionPolicy::default() };
+        let plan = evaluate(&rules, &gens, now());
+        assert_eq!(plan.to_delete(), vec![3]);
+
+        let rules = RetentionPolicy { keep_last: Some(2), ..policy() };
+        let plan = evaluate(&rules, &gens, now());
+        assert_eq!(plan.to_delete(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_keep_days() {
+        let gens = vec![gen(3, 1), gen(2, 10), gen(1, 40)];
+        let rules = RetentionPolicy { keep_days: Some(14), ..policy() };
+        assert_eq!(evaluate(&rules, &gens, now()).to_delete(), vec![1]);
+    }
+
+    #[test]
+    fn test_keep_weekly_and_monthly() {
+        // Two generations in each of the last three months
+        let gens = vec![gen(6, 0), gen(5, 2), gen(4, 31), gen(3, 33), gen(2, 61), gen(1, 63)];
+
+        let rules = RetentionPolicy { keep_monthly: Some(2), ..policy() };
+        assert_eq!(evaluate(&rules, &gens, now()).to_delete(), vec![5, 3, 2, 1]);
+
+        let rules = RetentionPolicy { keep_weekly: Some(3), ..policy() };
+        let plan = evaluate(&rules, &gens, now());
+        assert_eq!(plan.to_delete(), vec![5, 3, 1]);
+        assert_eq!(plan.decisions[0].reasons, vec![KeepReason::Weekly]);
+    }
+}
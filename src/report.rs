@@ -0,0 +1,402 @@
+//! Generation reports
+//!
+//! Renders a self-contained Markdown or HTML document with the
+//! generation list, pinned generations and (optionally) the diff between
+//! two generations, for keeping change records. Used by `nixhist report`
+//! and the Diff tab.
+
+use crate::config::Config;
+use crate::types::{DiffSide, Generation, GenerationDiff, ProfileId};
+use chrono::{DateTime, Local};
+
+/// Output format of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// File extension for reports in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Diff between two generations (or store paths) included in a report
+pub struct ReportDiff<'a> {
+    pub from: DiffSide,
+    pub to: DiffSide,
+    pub diff: &'a GenerationDiff,
+}
+
+/// Everything that goes into a report
+pub struct Report<'a> {
+    pub hostname: &'a str,
+    pub profile: ProfileId,
+    pub generations: &'a [Generation],
+    /// Pinned generation IDs with their notes
+    pub pins: Vec<(u32, Option<String>)>,
+    pub diff: Option<ReportDiff<'a>>,
+    pub created: DateTime<Local>,
+}
+
+/// Pinned generations of a profile with their notes
+pub fn pins(config: &Config, profile: &ProfileId) -> Vec<(u32, Option<String>)> {
+    config
+        .pinned_ids(profile)
+        .into_iter()
+        .map(|id| (id, config.pin_note(profile, id).map(str::to_string)))
+        .collect()
+}
+
+impl Report<'_> {
+    /// Render the report
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+
+    fn title(&self) -> String {
+        format!("{} generations on {}", self.profile.name(), self.hostname)
+    }
+
+    /// Rows of the generation table (same fields as `Generation`)
+    fn generation_rows(&self) -> Vec<[String; 8]> {
+        self.generations
+            .iter()
+            .map(|gen| {
+                let mut status = Vec::new();
+                if gen.is_current {
+                    status.push("current");
+                }
+                if gen.is_pinned {
+                    status.push("pinned");
+                }
+                if gen.in_bootloader {
+                    status.push("boot");
+                }
+                [
+                    gen.id.to_string(),
+                    gen.date.format("%Y-%m-%d %H:%M").to_string(),
+                    gen.nixos_version.clone().unwrap_or_default(),
+                    gen.kernel_version.clone().unwrap_or_default(),
+                    gen.package_count.to_string(),
+                    gen.formatted_size(),
+                    status.join(", "),
+                    gen.store_path.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    /// Kernel and security updates, called out before the full diff
+    fn highlights(diff: &GenerationDiff) -> Vec<String> {
+        diff.updated
+            .iter()
+            .filter(|u| u.is_kernel || u.is_security)
+            .map(|u| {
+                format!(
+                    "{} update: {} {} → {}",
+                    if u.is_kernel { "Kernel" } else { "Security" },
+                    u.name,
+                    u.old_version,
+                    u.new_version
+                )
+            })
+            .collect()
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+        out.push_str(&format!(
+            "Generated by nixhist on {}.\n\n",
+            self.created.format("%Y-%m-%d %H:%M")
+        ));
+
+        if let Some(d) = &self.diff {
+            out.push_str(&format!("## Changes {} → {}\n\n", d.from, d.to));
+            out.push_str(&format!("{}\n\n", d.diff.summary()));
+
+            let highlights = Self::highlights(d.diff);
+            if !highlights.is_empty() {
+                for line in highlights {
+                    out.push_str(&format!("> ⚠ **{}**\n", md_escape(&line)));
+                }
+                out.push('\n');
+            }
+
+            if !d.diff.added.is_empty() {
+                out.push_str(&format!("### Added ({})\n\n| Package | Version |\n|---|---|\n", d.diff.added.len()));
+                for pkg in &d.diff.added {
+                    out.push_str(&format!("| {} | {} |\n", md_escape(&pkg.name), md_escape(&pkg.version)));
+                }
+                out.push('\n');
+            }
+            if !d.diff.removed.is_empty() {
+                out.push_str(&format!("### Removed ({})\n\n| Package | Version |\n|---|---|\n", d.diff.removed.len()));
+                for pkg in &d.diff.removed {
+                    out.push_str(&format!("| {} | {} |\n", md_escape(&pkg.name), md_escape(&pkg.version)));
+                }
+                out.push('\n');
+            }
+            if !d.diff.updated.is_empty() {
+                out.push_str(&format!(
+                    "### Updated ({})\n\n| Package | Old | New | |\n|---|---|---|---|\n",
+                    d.diff.updated.len()
+                ));
+                for upd in &d.diff.updated {
+                    let flag = if upd.is_kernel { "⚠ Kernel" } else if upd.is_security { "⚠ Security" } else { "" };
+                    out.push_str(&format!(
+                        "| {} | {} | {} | {} |\n",
+                        md_escape(&upd.name),
+                        md_escape(&upd.old_version),
+                        md_escape(&upd.new_version),
+                        flag
+                    ));
+                }
+                out.push('\n');
+            }
+        }
+
+        out.push_str("## Pinned generations\n\n");
+        if self.pins.is_empty() {
+            out.push_str("None.\n\n");
+        } else {
+            for (id, note) in &self.pins {
+                match note {
+                    Some(note) => out.push_str(&format!("- #{}: {}\n", id, md_escape(note))),
+                    None => out.push_str(&format!("- #{}\n", id)),
+                }
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Generations\n\n");
+        out.push_str("| Gen | Date | Version | Kernel | Packages | Size | Status | Store path |\n");
+        out.push_str("|---:|---|---|---|---:|---:|---|---|\n");
+        for row in self.generation_rows() {
+            let cells: Vec<String> = row.iter().map(|c| md_escape(c)).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+
+        out
+    }
+
+    fn to_html(&self) -> String {
+        let mut body = format!("<h1>{}</h1>\n", html_escape(&self.title()));
+        body.push_str(&format!(
+            "<p class=\"meta\">Generated by nixhist on {}.</p>\n",
+            self.created.format("%Y-%m-%d %H:%M")
+        ));
+
+        if let Some(d) = &self.diff {
+            body.push_str(&format!(
+                "<h2>Changes {} → {}</h2>\n",
+                html_escape(&d.from.to_string()),
+                html_escape(&d.to.to_string())
+            ));
+            body.push_str(&format!("<p>{}</p>\n", html_escape(&d.diff.summary())));
+
+            for line in Self::highlights(d.diff) {
+                body.push_str(&format!("<p class=\"warning\">⚠ {}</p>\n", html_escape(&line)));
+            }
+
+            let package_table = |title: &str, class: &str, rows: Vec<[&str; 2]>| {
+                let mut table = format!(
+                    "<h3 class=\"{}\">{} ({})</h3>\n<table>\n<tr><th>Package</th><th>Version</th></tr>\n",
+                    class, title, rows.len()
+                );
+                for [name, version] in rows {
+                    table.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td></tr>\n",
+                        html_escape(name),
+                        html_escape(version)
+                    ));
+                }
+                table.push_str("</table>\n");
+                table
+            };
+
+            if !d.diff.added.is_empty() {
+                let rows = d.diff.added.iter().map(|p| [p.name.as_str(), p.version.as_str()]).collect();
+                body.push_str(&package_table("Added", "added", rows));
+            }
+            if !d.diff.removed.is_empty() {
+                let rows = d.diff.removed.iter().map(|p| [p.name.as_str(), p.version.as_str()]).collect();
+                body.push_str(&package_table("Removed", "removed", rows));
+            }
+            if !d.diff.updated.is_empty() {
+                body.push_str(&format!(
+                    "<h3 class=\"updated\">Updated ({})</h3>\n<table>\n<tr><th>Package</th><th>Old</th><th>New</th><th></th></tr>\n",
+                    d.diff.updated.len()
+                ));
+                for upd in &d.diff.updated {
+                    let flag = if upd.is_kernel { "⚠ Kernel" } else if upd.is_security { "⚠ Security" } else { "" };
+                    body.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"warning\">{}</td></tr>\n",
+                        html_escape(&upd.name),
+                        html_escape(&upd.old_version),
+                        html_escape(&upd.new_version),
+                        flag
+                    ));
+                }
+                body.push_str("</table>\n");
+            }
+        }
+
+        body.push_str("<h2>Pinned generations</h2>\n");
+        if self.pins.is_empty() {
+            body.push_str("<p>None.</p>\n");
+        } else {
+            body.push_str("<ul>\n");
+            for (id, note) in &self.pins {
+                match note {
+                    Some(note) => body.push_str(&format!("<li>#{}: {}</li>\n", id, html_escape(note))),
+                    None => body.push_str(&format!("<li>#{}</li>\n", id)),
+                }
+            }
+            body.push_str("</ul>\n");
+        }
+
+        body.push_str("<h2>Generations</h2>\n<table>\n<tr><th>Gen</th><th>Date</th><th>Version</th>\
+                       <th>Kernel</th><th>Packages</th><th>Size</th><th>Status</th><th>Store path</th></tr>\n");
+        for row in self.generation_rows() {
+            body.push_str("<tr>");
+            for cell in row {
+                body.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+            }
+            body.push_str("</tr>\n");
+        }
+        body.push_str("</table>\n");
+
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            html_escape(&self.title()),
+            HTML_STYLE,
+            body
+        )
+    }
+}
+
+/// Inline stylesheet so HTML reports are self-contained
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; max-width: 72rem; margin: 2rem auto; color: #282828; }
+table { border-collapse: collapse; margin-bottom: 1.5rem; }
+th, td { border: 1px solid #d5c4a1; padding: 0.25rem 0.6rem; text-align: left; }
+th { background: #ebdbb2; }
+.meta { color: #7c6f64; }
+.added { color: #79740e; }
+.removed { color: #9d0006; }
+.updated { color: #b57614; }
+.warning { color: #8f3f71; font-weight: bold; }
+";
+
+/// Escape text for Markdown (table cells, list items and emphasis)
+fn md_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '<' | '>' | '[' | ']' | '`' | '*' | '_' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape text for HTML
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Package;
+    use chrono::TimeZone;
+
+    fn pkg(name: &str, version: &str) -> Package {
+        Package { name: name.into(), version: version.into(), size: 0, attr_path: None, outputs: Vec::new() }
+    }
+
+    fn generation(id: u32) -> Generation {
+        Generation {
+            id,
+            date: Local.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+            is_current: id == 142,
+            nixos_version: Some("24.05.20240301".into()),
+            kernel_version: Some("6.6.52".into()),
+            package_count: 1200,
+            closure_size: 5 * 1024 * 1024 * 1024,
+            closure_count: None,
+            store_path: format!("/nix/store/xxx-nixos-system-host-{}", id),
+            is_pinned: id == 140,
+            in_bootloader: true,
+            specialisations: Vec::new(),
+        }
+    }
+
+    fn report<'a>(generations: &'a [Generation], diff: &'a GenerationDiff) -> Report<'a> {
+        Report {
+            hostname: "host",
+            profile: ProfileId::SYSTEM,
+            generations,
+            pins: vec![(140, Some("known <good>".into()))],
+            diff: Some(ReportDiff { from: DiffSide::generation(140), to: DiffSide::generation(142), diff }),
+            created: Local.with_ymd_and_hms(2024, 3, 2, 8, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let gens = vec![generation(142), generation(140)];
+        let diff = GenerationDiff::calculate(
+            &[pkg("linux", "6.6.51"), pkg("foo", "1.0")],
+            &[pkg("linux", "6.6.52"), pkg("foo", "1.0"), pkg("bar", "2.0")],
+        );
+
+        let md = report(&gens, &diff).render(ReportFormat::Markdown);
+        assert!(md.starts_with("# System generations on host\n"));
+        assert!(md.contains("## Changes #140 → #142"));
+        assert!(md.contains("> ⚠ **Kernel update: linux 6.6.51 → 6.6.52**"));
+        assert!(md.contains("| bar | 2.0 |"));
+        assert!(md.contains("- #140: known \\<good\\>"));
+        assert!(md.contains("| 140 | 2024-03-01 10:00 | 24.05.20240301 | 6.6.52 | 1200 | 5.0 GB | pinned, boot |"));
+    }
+
+    #[test]
+    fn test_markdown_report_is_escaped() {
+        let gens = vec![generation(142)];
+        let diff = GenerationDiff::calculate(&[], &[pkg("python3.12-foo_bar*", "[1.0]|`rc`")]);
+
+        let md = report(&gens, &diff).render(ReportFormat::Markdown);
+        assert!(md.contains("| python3.12-foo\\_bar\\* | \\[1.0\\]\\|\\`rc\\` |"));
+        assert_eq!(md_escape("a\\b\nc"), "a\\\\b c");
+    }
+
+    #[test]
+    fn test_html_report_is_escaped() {
+        let gens = vec![generation(142)];
+        let diff = GenerationDiff::default();
+
+        let html = report(&gens, &diff).render(ReportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("<li>#140: known &lt;good&gt;</li>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+}
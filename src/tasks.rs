@@ -0,0 +1,283 @@
+//! Background tasks
+//!
+//! Nix queries that can take seconds (listing generations, reading the
+//! package lists for the Packages and Diff tabs, a package's version
+//! history, tracing dependency chains) run on a worker thread.
+//! The worker reports progress and results over a channel, which the main
+//! loop drains every tick, so the UI keeps drawing and Esc can cancel.
+//!
+//! Cancelling stops the worker at its next step and drops whatever it
+//! still sends; a `nix` process that is already running finishes in the
+//! background.
+
+use crate::nix::closure::closure_diff;
+use crate::nix::{package_history, GenerationSource, HistoryEntry, NixBackend, PackageCache, WhyNode};
+use crate::types::{DiffSide, Generation, GenerationDiff, Package, ProfileId};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// Progress of a task: `done` of `total` steps finished, `label` names the next one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub label: String,
+}
+
+/// Message from a worker to the UI
+#[derive(Debug)]
+pub enum TaskEvent {
+    Progress(Progress),
+    /// Generations of one profile (sent as each profile finishes)
+    Profile { source: GenerationSource, generations: anyhow::Result<Vec<Generation>> },
+    /// Packages of a generation (or one of its specialisations)
+    Packages { profile: ProfileId, gen_id: u32, specialisation: Option<String>, packages: Vec<Package> },
+    /// Diff between two generations of a profile
+    Diff { profile: ProfileId, from: DiffSide, to: DiffSide, diff: anyhow::Result<GenerationDiff> },
+    /// Version history of a package, newest change first
+    History { package: String, entries: Vec<HistoryEntry> },
+    /// Dependency chains to packages (None if none of them is in the closure)
+    Why { title: String, tree: anyhow::Result<Option<WhyNode>> },
+    /// The worker finished
+    Done,
+}
+
+/// Handle of a running background task
+pub struct Task {
+    receiver: Receiver<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Task {
+    /// Run `work` on a worker thread
+    pub fn spawn(work: impl FnOnce(&Reporter) + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let reporter = Reporter { sender, cancelled: Arc::clone(&cancelled) };
+
+        thread::spawn(move || {
+            work(&reporter);
+            reporter.send(TaskEvent::Done);
+        });
+
+        Self { receiver, cancelled }
+    }
+
+    /// Ask the worker to stop at its next step
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Events received so far, without blocking
+    ///
+    /// A worker that died without sending `Done` (a panic) yields an
+    /// error after its last event.
+    pub fn poll(&self) -> Result<Vec<TaskEvent>, String> {
+        let mut events = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => return Ok(events),
+                Err(TryRecvError::Disconnected) => {
+                    if matches!(events.last(), Some(TaskEvent::Done)) {
+                        return Ok(events);
+                    }
+                    return Err("Background task stopped unexpectedly".into());
+                }
+            }
+        }
+    }
+}
+
+/// Worker side of a task
+pub struct Reporter {
+    sender: Sender<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Reporter {
+    /// Whether the UI cancelled the task
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Send an event (ignored once the UI has gone away)
+    pub fn send(&self, event: TaskEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Report progress before starting step `done + 1`
+    pub fn progress(&self, done: usize, total: usize, label: impl Into<String>) {
+        self.send(TaskEvent::Progress(Progress { done, total, label: label.into() }));
+    }
+}
+
+/// List the generations of each profile, one event per profile
+///
+/// Progress is reported per generation. With `closure_counts`, also counts
+/// the store paths in each closure.
+pub fn load_generations(
+    backend: Arc<dyn NixBackend>,
+    sources: Vec<GenerationSource>,
+    closure_counts: bool,
+) -> Task {
+    Task::spawn(move |reporter| {
+        for source in sources {
+            if reporter.is_cancelled() {
+                return;
+            }
+            let label = format!("{} generations", source.id.name());
+            let mut generations = backend.list_generations_with_progress(&source, &mut |done, total| {
+                reporter.progress(done, total, label.as_str());
+            });
+            if let (true, Ok(generations)) = (closure_counts, &mut generations) {
+                if reporter.is_cancelled() {
+                    return;
+                }
+                reporter.progress(generations.len(), generations.len(), format!("{} closures", source.id.name()));
+                count_closures(backend.as_ref(), &source, generations);
+            }
+            reporter.send(TaskEvent::Profile { source, generations });
+        }
+    })
+}
+
+/// Fill in the closure counts that are not known yet (unreadable ones stay unknown)
+fn count_closures(backend: &dyn NixBackend, source: &GenerationSource, generations: &mut [Generation]) {
+    let paths: Vec<PathBuf> = generations
+        .iter()
+        .filter(|g| g.closure_count.is_none())
+        .map(|g| source.generation_path(g.id))
+        .collect();
+    let counts = backend.closure_counts(&paths);
+    for gen in generations.iter_mut().filter(|g| g.closure_count.is_none()) {
+        gen.closure_count = counts.get(&source.generation_path(gen.id)).copied();
+    }
+}
+
+/// Read the packages of a generation (or one of its specialisations)
+pub fn load_packages(
+    backend: Arc<dyn NixBackend>,
+    source: GenerationSource,
+    profile: ProfileId,
+    gen_id: u32,
+    specialisation: Option<String>,
+) -> Task {
+    Task::spawn(move |reporter| {
+        let label = match &specialisation {
+            Some(name) => format!("#{} ({})", gen_id, name),
+            None => format!("#{}", gen_id),
+        };
+        reporter.progress(0, 1, label);
+        let path = source.configuration_path(gen_id, specialisation.as_deref());
+        let packages = backend.get_packages(&path).unwrap_or_default();
+        if !reporter.is_cancelled() {
+            reporter.send(TaskEvent::Packages { profile, gen_id, specialisation, packages });
+        }
+    })
+}
+
+/// Read the packages of two generations of a profile (or store paths) and compare them
+///
+/// With `full_closure`, every store path of the two closures is compared
+/// instead (see `nix::closure`).
+pub fn load_diff(
+    backend: Arc<dyn NixBackend>,
+    source: GenerationSource,
+    from: DiffSide,
+    to: DiffSide,
+    full_closure: bool,
+) -> Task {
+    if full_closure {
+        return load_closure_diff(backend, source, from, to);
+    }
+
+    Task::spawn(move |reporter| {
+        let mut lists = Vec::with_capacity(2);
+        let mut sizes = Vec::with_capacity(2);
+        for (done, side) in [&from, &to].into_iter().enumerate() {
+            if reporter.is_cancelled() {
+                return;
+            }
+            reporter.progress(done, 2, side.to_string());
+            let path = source.side_path(side);
+            lists.push(backend.get_packages(&path).unwrap_or_default());
+            sizes.push(backend.closure_size(&path).unwrap_or(0));
+        }
+
+        let diff = GenerationDiff::calculate(&lists[0], &lists[1]).with_closure_sizes(sizes[0], sizes[1]);
+        if !reporter.is_cancelled() {
+            reporter.send(TaskEvent::Diff { profile: source.id, from, to, diff: Ok(diff) });
+        }
+    })
+}
+
+/// Read the closures of two generations (or store paths) and compare them path by path
+fn load_closure_diff(backend: Arc<dyn NixBackend>, source: GenerationSource, from: DiffSide, to: DiffSide) -> Task {
+    Task::spawn(move |reporter| {
+        let mut graphs = Vec::with_capacity(2);
+        for (done, side) in [&from, &to].into_iter().enumerate() {
+            if reporter.is_cancelled() {
+                return;
+            }
+            reporter.progress(done, 2, format!("closure of {}", side));
+            let graph = backend
+                .closure_graph(&source.side_path(side))
+                .map_err(|e| e.context(format!("Failed to read the closure of {}", side)));
+            let failed = graph.is_err();
+            graphs.push(graph);
+            if failed {
+                break;
+            }
+        }
+
+        let diff = match graphs.as_slice() {
+            [Ok(old), Ok(new)] => Ok(closure_diff(old, new)),
+            _ => Err(graphs.into_iter().find_map(Result::err).expect("a closure failed to load")),
+        };
+        if !reporter.is_cancelled() {
+            reporter.send(TaskEvent::Diff { profile: source.id, from, to, diff });
+        }
+    })
+}
+
+/// Version history of a package over the generations of a profile
+///
+/// Progress is reported per generation; package lists already in `cache`
+/// are not read again.
+pub fn load_history(
+    backend: Arc<dyn NixBackend>,
+    source: GenerationSource,
+    generations: Vec<Generation>,
+    package: String,
+    cache: Arc<Mutex<PackageCache>>,
+) -> Task {
+    Task::spawn(move |reporter| {
+        let label = format!("{} generations", source.id.name());
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut progress = |done, total| {
+            reporter.progress(done, total, label.as_str());
+            !reporter.is_cancelled()
+        };
+        let history = package_history(backend.as_ref(), &package, &generations, &source, &mut cache, &mut progress);
+        if let (Some(mut entries), false) = (history, reporter.is_cancelled()) {
+            entries.reverse();
+            reporter.send(TaskEvent::History { package, entries });
+        }
+    })
+}
+
+/// Trace why packages are in the closure of a generation (or store path)
+pub fn load_why(backend: Arc<dyn NixBackend>, gen_path: PathBuf, packages: Vec<String>, title: String) -> Task {
+    Task::spawn(move |reporter| {
+        reporter.progress(0, 1, "closure");
+        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+        let tree = backend.why_depends(&gen_path, &packages);
+        if !reporter.is_cancelled() {
+            reporter.send(TaskEvent::Why { title, tree });
+        }
+    })
+}
@@ -2,9 +2,11 @@
 //! 
 //! This module defines all shared data structures used throughout the application.
 
+use crate::nix::version::classify_update;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::path::PathBuf;
 
 /// Represents a NixOS or Home-Manager generation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,9 +18,15 @@ pub struct Generation {
     pub kernel_version: Option<String>,
     pub package_count: usize,
     pub closure_size: u64,
+    /// Number of store paths in the closure (only read when enabled in Settings)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closure_count: Option<usize>,
     pub store_path: String,
     pub is_pinned: bool,
     pub in_bootloader: bool,
+    /// NixOS specialisations (`specialisation/<name>`) of a system generation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub specialisations: Vec<String>,
 }
 
 impl Generation {
@@ -39,6 +47,12 @@ pub struct Package {
     pub name: String,
     pub version: String,
     pub size: u64,
+    /// Flake attribute path (`nix profile` elements only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attr_path: Option<String>,
+    /// Outputs in the closure with their sizes (empty unless there are several)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<PackageOutput>,
 }
 
 impl Package {
@@ -46,14 +60,46 @@ impl Package {
     pub fn formatted_size(&self) -> String {
         format_bytes(self.size)
     }
+
+    /// Format the outputs for display (e.g., "out 1.2 MB, man 40.0 KB")
+    pub fn formatted_outputs(&self) -> String {
+        self.outputs
+            .iter()
+            .map(|o| format!("{} {}", o.name, format_bytes(o.size)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// One output (`out`, `bin`, `man`, ...) of a package
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct PackageOutput {
+    pub name: String,
+    pub size: u64,
 }
 
 /// Result of comparing two generations
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct GenerationDiff {
     pub added: Vec<Package>,
     pub removed: Vec<Package>,
     pub updated: Vec<PackageUpdate>,
+    /// Same version, new store path (full-closure diffs only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rebuilt: Vec<PackageRebuild>,
+    /// Growth (or, if negative, shrink) of the closure in bytes, if both sizes are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closure_change: Option<i64>,
+}
+
+/// Order of the entries within each section of a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DiffSort {
+    /// Alphabetical by package name
+    #[default]
+    Name,
+    /// Largest size change first
+    Size,
 }
 
 impl GenerationDiff {
@@ -79,46 +125,212 @@ impl GenerationDiff {
         for new_pkg in new_packages {
             if let Some(old_pkg) = old_packages.iter().find(|p| p.name == new_pkg.name) {
                 if old_pkg.version != new_pkg.version {
-                    updated.push(PackageUpdate {
-                        name: new_pkg.name.clone(),
-                        old_version: old_pkg.version.clone(),
-                        new_version: new_pkg.version.clone(),
-                        is_kernel: new_pkg.name.starts_with("linux-"),
-                        is_security: is_security_package(&new_pkg.name),
-                    });
+                    updated.push(PackageUpdate::new(old_pkg, new_pkg));
                 }
             }
         }
 
-        Self { added, removed, updated }
+        let mut diff = Self { added, removed, updated, rebuilt: Vec::new(), closure_change: None };
+        diff.sort(DiffSort::Name);
+        diff
+    }
+
+    /// Record the closure sizes of both sides (ignored if either is unknown)
+    pub fn with_closure_sizes(mut self, old_size: u64, new_size: u64) -> Self {
+        if old_size > 0 && new_size > 0 {
+            self.closure_change = Some(size_change(old_size, new_size));
+        }
+        self
+    }
+
+    /// Sort the added, removed and updated packages
+    pub fn sort(&mut self, order: DiffSort) {
+        match order {
+            DiffSort::Name => {
+                self.added.sort_by(|a, b| a.name.cmp(&b.name));
+                self.removed.sort_by(|a, b| a.name.cmp(&b.name));
+                self.updated.sort_by(|a, b| a.name.cmp(&b.name));
+                self.rebuilt.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+            DiffSort::Size => {
+                self.added.sort_by_key(|p| std::cmp::Reverse(p.size));
+                self.removed.sort_by_key(|p| std::cmp::Reverse(p.size));
+                self.updated.sort_by_key(|u| std::cmp::Reverse(u.size_change().unsigned_abs()));
+            }
+        }
+    }
+
+    /// Check if any updated package is a kernel
+    pub fn has_kernel_update(&self) -> bool {
+        self.updated.iter().any(|u| u.is_kernel)
+    }
+
+    /// Check if any updated package is security-related
+    pub fn has_security_update(&self) -> bool {
+        self.updated.iter().any(|u| u.is_security)
+    }
+
+    /// Check if the two generations have identical package sets
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty() && self.rebuilt.is_empty()
     }
 
-    /// Get summary string (e.g., "+8 -3 ~24")
+    /// Get summary string (e.g., "+8 added · -3 removed · ~24 updated · closure +812.4 MB")
     pub fn summary(&self) -> String {
-        format!(
+        let mut summary = format!(
             "+{} added · -{} removed · ~{} updated",
             self.added.len(),
             self.removed.len(),
             self.updated.len()
-        )
+        );
+        if !self.rebuilt.is_empty() {
+            summary.push_str(&format!(" · ⟳{} rebuilt", self.rebuilt.len()));
+        }
+        if let Some(change) = self.closure_change {
+            summary.push_str(&format!(" · closure {}", format_size_change(change)));
+        }
+        summary
+    }
+}
+
+/// One side of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSide {
+    /// A generation, or one of its NixOS specialisations
+    Generation { id: u32, specialisation: Option<String> },
+    /// Any store path or link to one (e.g. `./result` of a build)
+    Path(PathBuf),
+}
+
+impl DiffSide {
+    /// The base configuration of a generation
+    pub fn generation(id: u32) -> Self {
+        DiffSide::Generation { id, specialisation: None }
+    }
+
+    /// Generation ID, unless the side is a path
+    pub fn gen_id(&self) -> Option<u32> {
+        match self {
+            DiffSide::Generation { id, .. } => Some(*id),
+            DiffSide::Path(_) => None,
+        }
+    }
+
+    /// Selected specialisation of a generation side
+    pub fn specialisation(&self) -> Option<&str> {
+        match self {
+            DiffSide::Generation { specialisation, .. } => specialisation.as_deref(),
+            DiffSide::Path(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DiffSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffSide::Generation { id, specialisation: Some(name) } => write!(f, "#{} ({})", id, name),
+            DiffSide::Generation { id, specialisation: None } => write!(f, "#{}", id),
+            DiffSide::Path(path) => write!(f, "{}", path.display()),
+        }
     }
 }
 
 /// Represents a package version update
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PackageUpdate {
     pub name: String,
     pub old_version: String,
     pub new_version: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub kind: UpdateKind,
     pub is_kernel: bool,
     pub is_security: bool,
 }
 
-/// Profile type (System or Home-Manager)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// A package rebuilt with the same version because a dependency changed
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageRebuild {
+    pub name: String,
+    pub version: String,
+    /// Changed dependencies behind the rebuild (`openssl 3.0.14`); empty if
+    /// no runtime dependency changed, i.e. only the build itself did
+    pub caused_by: Vec<String>,
+}
+
+/// Kind of version change of an updated package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateKind {
+    /// First version component changed
+    Major,
+    /// Second version component changed
+    Minor,
+    /// Any later component changed
+    Patch,
+    /// The new version is older
+    Downgrade,
+    /// Either version is a pre-release (`pre`, `rc`, `alpha`, `beta`)
+    PreRelease,
+    /// Either version is an unstable snapshot (`0-unstable-2024-05-01`)
+    UnstableDate,
+}
+
+impl UpdateKind {
+    pub const ALL: [UpdateKind; 6] = [
+        UpdateKind::Major,
+        UpdateKind::Minor,
+        UpdateKind::Patch,
+        UpdateKind::Downgrade,
+        UpdateKind::PreRelease,
+        UpdateKind::UnstableDate,
+    ];
+
+    /// Short name for display
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpdateKind::Major => "major",
+            UpdateKind::Minor => "minor",
+            UpdateKind::Patch => "patch",
+            UpdateKind::Downgrade => "downgrade",
+            UpdateKind::PreRelease => "pre-release",
+            UpdateKind::UnstableDate => "unstable-date",
+        }
+    }
+}
+
+impl PackageUpdate {
+    /// Update from one version of a package to another
+    pub fn new(old: &Package, new: &Package) -> Self {
+        Self {
+            name: new.name.clone(),
+            old_version: old.version.clone(),
+            new_version: new.version.clone(),
+            old_size: old.size,
+            new_size: new.size,
+            kind: classify_update(&old.version, &new.version),
+            is_kernel: is_kernel_package(&new.name),
+            is_security: is_security_package(&new.name),
+        }
+    }
+
+    /// Size change in bytes (negative if the package shrank)
+    pub fn size_change(&self) -> i64 {
+        size_change(self.old_size, self.new_size)
+    }
+}
+
+/// Profile type (System, Home-Manager, a `nix profile` user profile or a plain nix-env profile)
+///
+/// Decides how generations are read, restored and deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
 pub enum ProfileType {
     System,
     HomeManager,
+    NixProfile,
+    /// Only used for profiles declared in config.toml
+    #[value(skip)]
+    NixEnv,
 }
 
 impl ProfileType {
@@ -126,12 +338,54 @@ impl ProfileType {
         match self {
             ProfileType::System => "System",
             ProfileType::HomeManager => "Home-Manager",
+            ProfileType::NixProfile => "Nix Profile",
+            ProfileType::NixEnv => "nix-env",
         }
     }
 }
 
+/// Identifies a profile: a detected one, or one declared in config.toml
+///
+/// `Builtin` never holds `ProfileType::NixEnv`, which only describes how a
+/// configured profile is handled.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(untagged)]
+pub enum ProfileId {
+    Builtin(ProfileType),
+    Custom(String),
+}
+
+impl ProfileId {
+    pub const SYSTEM: ProfileId = ProfileId::Builtin(ProfileType::System);
+
+    /// Display name
+    pub fn name(&self) -> &str {
+        match self {
+            ProfileId::Builtin(profile_type) => profile_type.as_str(),
+            ProfileId::Custom(name) => name,
+        }
+    }
+
+    /// Parse a `--profile` value: a built-in profile or the name of a configured one
+    ///
+    /// Built-in names win (case-insensitively), which is why config.toml
+    /// may not use them for `[[profiles]]`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        use clap::ValueEnum;
+        Ok(ProfileType::from_str(s, true)
+            .map(ProfileId::Builtin)
+            .unwrap_or_else(|_| ProfileId::Custom(s.to_string())))
+    }
+}
+
+impl From<ProfileType> for ProfileId {
+    fn from(profile_type: ProfileType) -> Self {
+        ProfileId::Builtin(profile_type)
+    }
+}
+
 /// Application tabs
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
 pub enum Tab {
     #[default]
     Overview,
@@ -156,17 +410,6 @@ impl Tab {
         }
     }
 
-    pub fn from_index(idx: usize) -> Self {
-        match idx {
-            0 => Tab::Overview,
-            1 => Tab::Packages,
-            2 => Tab::Diff,
-            3 => Tab::Manage,
-            4 => Tab::Settings,
-            _ => Tab::Overview,
-        }
-    }
-
     pub fn label(&self) -> &'static str {
         match self {
             Tab::Overview => "Overview",
@@ -197,6 +440,31 @@ pub fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// Format a size change with its sign (e.g., "+1.2 MB", "-40.0 KB")
+pub fn format_size_change(change: i64) -> String {
+    let sign = if change < 0 { '-' } else { '+' };
+    format!("{}{}", sign, format_bytes(change.unsigned_abs()))
+}
+
+/// Format an entry's size with its change (e.g., "4.0 MB (+1.2 MB)")
+pub fn format_size_with_change(size: u64, change: i64) -> String {
+    format!("{} ({})", format_bytes(size), format_size_change(change))
+}
+
+/// Signed difference between two sizes
+fn size_change(old_size: u64, new_size: u64) -> i64 {
+    (new_size as i64).saturating_sub(old_size as i64)
+}
+
+/// Check if a package is the kernel (store name `linux-<version>`)
+///
+/// Kernel variants (`-rt`, `-zen`, `-hardened`) keep the name `linux` and
+/// carry the variant in the version; `linux-pam`, `linux-firmware` or
+/// `linux-headers` are not kernels.
+fn is_kernel_package(name: &str) -> bool {
+    name == "linux"
+}
+
 /// Check if a package is security-related
 fn is_security_package(name: &str) -> bool {
     let security_packages = [
@@ -210,6 +478,14 @@ fn is_security_package(name: &str) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_kernel_package() {
+        assert!(is_kernel_package("linux"));
+        assert!(!is_kernel_package("linux-pam"));
+        assert!(!is_kernel_package("linux-firmware"));
+        assert!(!is_kernel_package("linux-headers"));
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(format_bytes(500), "500 B");
@@ -221,12 +497,12 @@ mod tests {
     #[test]
     fn test_generation_diff() {
         let old = vec![
-            Package { name: "foo".into(), version: "1.0".into(), size: 100 },
-            Package { name: "bar".into(), version: "2.0".into(), size: 200 },
+            Package { name: "foo".into(), version: "1.0".into(), size: 100, attr_path: None, outputs: Vec::new() },
+            Package { name: "bar".into(), version: "2.0".into(), size: 200, attr_path: None, outputs: Vec::new() },
         ];
         let new = vec![
-            Package { name: "foo".into(), version: "1.1".into(), size: 100 },
-            Package { name: "baz".into(), version: "1.0".into(), size: 150 },
+            Package { name: "foo".into(), version: "1.1".into(), size: 100, attr_path: None, outputs: Vec::new() },
+            Package { name: "baz".into(), version: "1.0".into(), size: 150, attr_path: None, outputs: Vec::new() },
         ];
 
         let diff = GenerationDiff::calculate(&old, &new);
@@ -234,4 +510,33 @@ mod tests {
         assert_eq!(diff.removed.len(), 1);
         assert_eq!(diff.updated.len(), 1);
     }
+
+    #[test]
+    fn test_diff_sizes() {
+        let pkg = |name: &str, version: &str, size: u64| Package {
+            name: name.into(),
+            version: version.into(),
+            size,
+            attr_path: None,
+            outputs: Vec::new(),
+        };
+        let old = vec![pkg("foo", "1.0", 4096), pkg("llvm", "17.0.6", 400_000_000), pkg("gone", "1.0", 10)];
+        let new = vec![pkg("foo", "1.1", 1024), pkg("llvm", "18.1.8", 500_000_000), pkg("bar", "2.0", 5)];
+
+        let mut diff = GenerationDiff::calculate(&old, &new).with_closure_sizes(9_000_000_000, 9_100_000_000);
+        assert_eq!(diff.updated.iter().map(|u| u.size_change()).collect::<Vec<_>>(), [-3072, 100_000_000]);
+        assert_eq!(diff.summary(), "+1 added · -1 removed · ~2 updated · closure +95.4 MB");
+
+        diff.sort(DiffSort::Size);
+        assert_eq!(diff.updated[0].name, "llvm");
+        diff.sort(DiffSort::Name);
+        assert_eq!(diff.updated[0].name, "foo");
+
+        assert_eq!(format_size_change(-3072), "-3.0 KB");
+        assert_eq!(format_size_with_change(1024, -3072), "1.0 KB (-3.0 KB)");
+        // Unknown closure sizes are left out of the summary
+        let diff = GenerationDiff::calculate(&old, &new).with_closure_sizes(0, 9_100_000_000);
+        assert_eq!(diff.closure_change, None);
+        assert!(!diff.summary().contains("closure"));
+    }
 }
@@ -7,15 +7,17 @@
 //! - Status bar
 
 use crate::app::{App, PopupState};
-use crate::types::{Generation, GenerationDiff, ProfileType, Tab};
+use crate::nix::{HistoryEntry, WhyNode};
+use crate::types::{format_size_with_change, DiffSide, DiffSort, Generation, GenerationDiff, Tab, UpdateKind};
 use crate::ui::{theme::Theme, widgets};
 use ratatui::{
     layout::{Alignment, Constraint, Layout, Rect},
     style::Style,
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Table, Tabs, Wrap},
+    widgets::{Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, Tabs, Wrap},
     Frame,
 };
+use std::collections::HashSet;
 
 /// Main render function - entry point for all UI rendering
 pub fn render(frame: &mut Frame, app: &App) {
@@ -100,9 +102,9 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     let theme = &app.theme;
     
     let hints = match app.active_tab {
-        Tab::Overview => "[j/k] Navigate  [Tab] Switch Panel  [Enter] View Packages  [?] Help  [q] Quit",
-        Tab::Packages => "[j/k] Navigate  [/] Filter  [Enter] History  [Esc] Back  [q] Quit",
-        Tab::Diff => "[Tab] Switch List  [j/k] Navigate  [Enter] Select  [c] Clear  [q] Quit",
+        Tab::Overview => "[j/k] Navigate  [Tab] Switch Panel  [Enter] View Packages  [Ctrl+R] Reload  [?] Help  [q] Quit",
+        Tab::Packages => "[j/k] Navigate  [/] Filter  [Enter] History  [w] Why  [s] Specialisation  [Esc] Back  [q] Quit",
+        Tab::Diff => "[Tab] Switch List  [j/k] Navigate  [Enter] Select  [o] Path  [p] Profile  [s] Specialisation  [z] Sort  [f] Filter  [m] Closure  [w] Why Added  [e/E] Export  [c] Clear  [q] Quit",
         Tab::Manage => "[Space] Select  [R] Restore  [D] Delete  [P] Pin  [q] Quit",
         Tab::Settings => "[j/k] Navigate  [Enter] Change  [q] Quit",
     };
@@ -136,8 +138,29 @@ fn render_popups(frame: &mut Frame, app: &App, area: Rect) {
             widgets::render_undo_popup(frame, message, *seconds_remaining, theme, area);
         }
         
-        PopupState::Loading { message } => {
-            widgets::render_loading(frame, message, theme, area);
+        PopupState::Loading { message, progress } => {
+            widgets::render_loading(frame, message, progress.as_ref(), theme, area);
+        }
+
+        PopupState::Why { title, tree, cursor, collapsed } => {
+            render_why_popup(frame, title, tree, *cursor, collapsed, theme, area);
+        }
+
+        PopupState::History { package, entries, scroll } => {
+            render_history_popup(frame, package, entries, *scroll, theme, area);
+        }
+
+        PopupState::PathInput { input } => {
+            let side = if app.diff_focus == 0 { "From" } else { "To" };
+            let content = vec![
+                Line::raw(""),
+                Line::raw(format!("Store path or link to compare as the {} side:", side)),
+                Line::raw(""),
+                Line::styled(format!("{}▏", input), theme.title()),
+                Line::raw(""),
+                Line::styled("[Enter] Compare  [Esc] Cancel", theme.text_dim()),
+            ];
+            widgets::render_popup(frame, "Compare Path", content, &[], theme, area);
         }
     }
 
@@ -147,60 +170,205 @@ fn render_popups(frame: &mut Frame, app: &App, area: Rect) {
     }
 }
 
+/// Dependency tree popup ("why is this package here?")
+fn render_why_popup(
+    frame: &mut Frame,
+    title: &str,
+    tree: &WhyNode,
+    cursor: usize,
+    collapsed: &HashSet<usize>,
+    theme: &Theme,
+    area: Rect,
+) {
+    let popup_area = widgets::centered_rect(
+        (area.width * 4 / 5).max(40).min(area.width),
+        (area.height * 4 / 5).max(10).min(area.height),
+        area,
+    );
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .style(theme.block_style())
+        .title(format!(" {} ", title))
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(theme.border_focused());
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let list_area = Rect { height: inner.height.saturating_sub(1), ..inner };
+    let rows = tree.visible_rows(collapsed);
+
+    // Keep the cursor in view
+    let height = list_area.height as usize;
+    let offset = cursor.saturating_sub(height.saturating_sub(1));
+
+    let lines: Vec<Line> = rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, row)| {
+            let marker = if row.node.children.is_empty() {
+                "  "
+            } else if collapsed.contains(&row.index) {
+                "▸ "
+            } else {
+                "▾ "
+            };
+            let name_style = if i == cursor {
+                theme.selected()
+            } else if row.node.is_target {
+                theme.diff_added()
+            } else {
+                theme.text()
+            };
+
+            let mut spans = vec![
+                Span::raw("  ".repeat(row.depth)),
+                Span::styled(marker, theme.text_dim()),
+                Span::styled(row.node.name.as_str(), name_style),
+            ];
+            if row.node.repeated {
+                spans.push(Span::styled(" (see above)", theme.text_dim()));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), list_area);
+
+    let hint_area = Rect {
+        y: inner.y + inner.height.saturating_sub(1),
+        height: 1,
+        ..inner
+    };
+    let hint = Paragraph::new("[j/k] Navigate  [Enter] Expand/Collapse  [Esc] Close")
+        .style(theme.text_dim())
+        .alignment(Alignment::Center);
+    frame.render_widget(hint, hint_area);
+}
+
+/// Package version history popup
+fn render_history_popup(
+    frame: &mut Frame,
+    package: &str,
+    entries: &[HistoryEntry],
+    scroll: usize,
+    theme: &Theme,
+    area: Rect,
+) {
+    let popup_area = widgets::centered_rect(
+        70.min(area.width),
+        (entries.len() as u16 + 6).max(8).min(area.height),
+        area,
+    );
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .style(theme.block_style())
+        .title(format!(" History · {} ", package))
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(theme.border_focused());
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let table_area = Rect { height: inner.height.saturating_sub(1), ..inner };
+    if entries.is_empty() {
+        let empty = Paragraph::new("Not found in any generation")
+            .style(theme.text_dim())
+            .alignment(Alignment::Center);
+        frame.render_widget(empty, table_area);
+    } else {
+        let header = Row::new(vec![
+            Cell::from("GEN").style(theme.title()),
+            Cell::from("DATE").style(theme.title()),
+            Cell::from("VERSION").style(theme.title()),
+            Cell::from("CHANGE").style(theme.title()),
+        ]);
+
+        let rows: Vec<Row> = entries
+            .iter()
+            .skip(scroll)
+            .map(|entry| {
+                let style = match entry.change_label() {
+                    "added" => theme.diff_added(),
+                    "removed" => theme.diff_removed(),
+                    _ => theme.diff_updated(),
+                };
+                Row::new(vec![
+                    Cell::from(format!("#{}", entry.gen_id)),
+                    Cell::from(entry.date.format("%Y-%m-%d").to_string()),
+                    Cell::from(entry.version_text()),
+                    Cell::from(entry.change_label()).style(style),
+                ])
+                .style(theme.text())
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(7),
+                Constraint::Length(11),
+                Constraint::Min(20),
+                Constraint::Length(10),
+            ],
+        )
+        .header(header);
+        frame.render_widget(table, table_area);
+    }
+
+    let hint_area = Rect {
+        y: inner.y + inner.height.saturating_sub(1),
+        height: 1,
+        ..inner
+    };
+    let hint = Paragraph::new("[j/k] Scroll  [Esc] Close")
+        .style(theme.text_dim())
+        .alignment(Alignment::Center);
+    frame.render_widget(hint, hint_area);
+}
+
 // === TAB RENDERERS ===
 
-/// Overview tab: System and Home-Manager generations side by side
+/// Overview tab: generations of each profile side by side
 fn render_overview_tab(frame: &mut Frame, app: &App, area: Rect) {
     let theme = &app.theme;
-    let has_hm = app.home_manager_generations.is_some();
+    let profiles = app.profiles();
 
     // Determine layout based on terminal width and config
-    let use_side_by_side = has_hm && app.should_use_side_by_side(area.width);
+    let use_side_by_side = profiles.len() > 1 && app.should_use_side_by_side(area.width);
 
     if use_side_by_side {
-        // Split horizontally for System | Home-Manager
-        let panels = Layout::horizontal([
-            Constraint::Percentage(50),
-            Constraint::Percentage(50),
-        ])
-        .split(area);
-
-        render_generation_list(
-            frame,
-            "System",
-            &app.system_generations,
-            app.overview_system_selected,
-            app.overview_focus == 0,
-            theme,
-            panels[0],
-        );
+        // One panel per profile: System | Home-Manager | ...
+        let count = profiles.len() as u32;
+        let panels = Layout::horizontal(profiles.iter().map(|_| Constraint::Ratio(1, count)))
+            .split(area);
 
-        if let Some(hm_gens) = &app.home_manager_generations {
+        for (i, (profile, &panel)) in profiles.iter().zip(panels.iter()).enumerate() {
             render_generation_list(
                 frame,
-                "Home-Manager",
-                hm_gens,
-                app.overview_hm_selected,
-                app.overview_focus == 1,
+                profile.name(),
+                app.generations_for(profile),
+                app.overview_cursor(profile),
+                app.overview_focus == i,
                 theme,
-                panels[1],
+                panel,
             );
         }
     } else {
         // Single panel view
-        let title = if app.overview_focus == 0 { "System" } else { "Home-Manager" };
-        let gens = if app.overview_focus == 0 {
-            &app.system_generations
-        } else {
-            app.home_manager_generations.as_ref().unwrap_or(&app.system_generations)
-        };
-        let selected = if app.overview_focus == 0 {
-            app.overview_system_selected
-        } else {
-            app.overview_hm_selected
-        };
-
-        render_generation_list(frame, title, gens, selected, true, theme, area);
+        let profile = app.overview_profile();
+        render_generation_list(
+            frame,
+            profile.name(),
+            app.generations_for(&profile),
+            app.overview_cursor(&profile),
+            true,
+            theme,
+            area,
+        );
     }
 }
 
@@ -296,13 +464,20 @@ fn render_generation_list(
             height: 2,
         };
 
-        let details = format!(
-            "{} · {} · {} pkgs · {}",
+        let packages = match gen.closure_count {
+            Some(count) => format!("{} pkgs ({} in closure)", gen.package_count, count),
+            None => format!("{} pkgs", gen.package_count),
+        };
+        let mut details = format!(
+            "{} · {} · {} · {}",
             gen.nixos_version.as_deref().unwrap_or("Unknown"),
             gen.kernel_version.as_deref().unwrap_or("-"),
-            gen.package_count,
+            packages,
             gen.formatted_size(),
         );
+        if !gen.specialisations.is_empty() {
+            details.push_str(&format!(" · specialisations: {}", gen.specialisations.join(", ")));
+        }
 
         let detail_widget = Paragraph::new(details)
             .style(theme.text_dim())
@@ -319,8 +494,13 @@ fn render_packages_tab(frame: &mut Frame, app: &App, area: Rect) {
     let block = Block::default()
         .style(theme.block_style())
         .title(format!(
-            " Packages · Generation #{} ",
-            app.packages_gen_id.unwrap_or(0)
+            " Packages · {} Generation #{}{} ",
+            app.packages_profile.name(),
+            app.packages_gen_id.unwrap_or(0),
+            app.packages_specialisation
+                .as_deref()
+                .map(|name| format!(" · specialisation {}", name))
+                .unwrap_or_default(),
         ))
         .title_style(theme.title())
         .borders(Borders::ALL)
@@ -364,12 +544,23 @@ fn render_packages_tab(frame: &mut Frame, app: &App, area: Rect) {
         return;
     }
 
+    // `nix profile` elements also show their flake attribute, multi-output
+    // packages the sizes of their outputs
+    let show_attr = app.packages_list.iter().any(|p| p.attr_path.is_some());
+    let show_outputs = app.packages_list.iter().any(|p| !p.outputs.is_empty());
+
     // Table header
-    let header = Row::new(vec![
+    let mut header = vec![
         Cell::from("NAME").style(theme.title()),
         Cell::from("VERSION").style(theme.title()),
         Cell::from("SIZE").style(theme.title()),
-    ]);
+    ];
+    if show_outputs {
+        header.push(Cell::from("OUTPUTS").style(theme.title()));
+    }
+    if show_attr {
+        header.push(Cell::from("ATTRIBUTE").style(theme.title()));
+    }
 
     // Table rows
     let rows: Vec<Row> = filtered
@@ -382,24 +573,48 @@ fn render_packages_tab(frame: &mut Frame, app: &App, area: Rect) {
                 theme.text()
             };
 
-            Row::new(vec![
+            let mut cells = vec![
                 Cell::from(pkg.name.clone()),
                 Cell::from(pkg.version.clone()),
                 Cell::from(pkg.formatted_size()),
-            ])
-            .style(style)
+            ];
+            if show_outputs {
+                cells.push(Cell::from(pkg.formatted_outputs()));
+            }
+            if show_attr {
+                cells.push(Cell::from(pkg.attr_path.clone().unwrap_or_default()));
+            }
+            Row::new(cells).style(style)
         })
         .collect();
 
-    let table = Table::new(
-        rows,
-        [
+    let widths = match (show_outputs, show_attr) {
+        (true, true) => vec![
+            Constraint::Percentage(20),
+            Constraint::Percentage(12),
+            Constraint::Percentage(10),
+            Constraint::Percentage(28),
+            Constraint::Percentage(30),
+        ],
+        (false, true) => vec![
+            Constraint::Percentage(25),
+            Constraint::Percentage(15),
+            Constraint::Percentage(10),
             Constraint::Percentage(50),
+        ],
+        (true, false) => vec![
             Constraint::Percentage(30),
             Constraint::Percentage(20),
+            Constraint::Percentage(10),
+            Constraint::Percentage(40),
         ],
-    )
-    .header(header);
+        (false, false) => vec![
+            Constraint::Percentage(50),
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+        ],
+    };
+    let table = Table::new(rows, widths).header(Row::new(header));
 
     frame.render_widget(table, list_area);
 
@@ -428,7 +643,7 @@ fn render_diff_tab(frame: &mut Frame, app: &App, area: Rect) {
     // Main block
     let block = Block::default()
         .style(theme.block_style())
-        .title(" Compare Generations ")
+        .title(format!(" Compare Generations · {} ", app.diff_profile.name()))
         .title_style(theme.title())
         .borders(Borders::ALL)
         .border_style(theme.border_focused());
@@ -458,60 +673,42 @@ fn render_diff_selection_lists(
     area: Rect,
 ) {
     // Split horizontally for From | To
-    let lists = Layout::horizontal([
-        Constraint::Percentage(50),
-        Constraint::Percentage(50),
-    ])
-    .split(area);
+    let lists = widgets::horizontal_split(area, &[50, 50]);
 
     // Render From list
-    render_diff_gen_list(
-        frame,
-        "From Generation",
-        &app.system_generations,
-        app.diff_from_cursor,
-        app.diff_from_gen,
-        app.diff_focus == 0,
-        theme,
-        lists[0],
-    );
+    render_diff_gen_list(frame, app, 0, theme, lists[0]);
 
     // Render To list
-    render_diff_gen_list(
-        frame,
-        "To Generation",
-        &app.system_generations,
-        app.diff_to_cursor,
-        app.diff_to_gen,
-        app.diff_focus == 1,
-        theme,
-        lists[1],
-    );
+    render_diff_gen_list(frame, app, 1, theme, lists[1]);
 }
 
-/// Render a single generation selection list
-fn render_diff_gen_list(
-    frame: &mut Frame,
-    title: &str,
-    generations: &[Generation],
-    cursor: usize,
-    selected_id: Option<u32>,
-    is_focused: bool,
-    theme: &Theme,
-    area: Rect,
-) {
+/// Render a single generation selection list (`side` 0 is From, 1 is To)
+fn render_diff_gen_list(frame: &mut Frame, app: &App, side: usize, theme: &Theme, area: Rect) {
+    let (title, cursor, selected) = if side == 0 {
+        ("From Generation", app.diff_from_cursor, app.diff_from.as_ref())
+    } else {
+        ("To Generation", app.diff_to_cursor, app.diff_to.as_ref())
+    };
+    let generations = app.generations_for(&app.diff_profile);
+    let is_focused = app.diff_focus == side;
     let border_style = if is_focused {
         theme.border_focused()
     } else {
         theme.border()
     };
 
-    // Add selected generation to title if one is selected
-    let full_title = if let Some(id) = selected_id {
-        format!(" {} (#{}) ", title, id)
-    } else {
-        format!(" {} ", title)
+    // Add selected generation (and specialisation) or path to title if one is selected
+    let full_title = match selected {
+        Some(DiffSide::Generation { id, specialisation: Some(name) }) => format!(" {} (#{} · {}) ", title, id, name),
+        Some(DiffSide::Generation { id, specialisation: None }) => format!(" {} (#{}) ", title, id),
+        Some(DiffSide::Path(path)) => format!(
+            " {} ({}) ",
+            title,
+            path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+        ),
+        None => format!(" {} ", title),
     };
+    let selected_id = selected.and_then(DiffSide::gen_id);
 
     let block = Block::default()
         .style(theme.block_style())
@@ -574,9 +771,23 @@ fn render_diff_results(
     theme: &Theme,
     area: Rect,
 ) {
+    // Name the compared profile and generations once both are selected
+    let title = match app.diff_sides() {
+        Some((from, to)) => {
+            let mut title = format!(" Diff Results · {} {} → {} ", app.diff_profile.name(), from, to);
+            if app.diff_full_closure {
+                title.push_str("· full closure ");
+            }
+            if app.diff_sort == DiffSort::Size {
+                title.push_str("· by size ");
+            }
+            title
+        }
+        None => " Diff Results ".to_string(),
+    };
     let block = Block::default()
         .style(theme.block_style())
-        .title(" Diff Results ")
+        .title(title)
         .title_style(theme.text_dim())
         .borders(Borders::ALL)
         .border_style(theme.border());
@@ -584,8 +795,8 @@ fn render_diff_results(
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
-    if app.diff_from_gen.is_none() || app.diff_to_gen.is_none() {
-        let hint = Paragraph::new("Select two generations to compare\n\n[Tab] Switch list  [j/k] Navigate  [Enter] Select")
+    if app.diff_sides().is_none() {
+        let hint = Paragraph::new("Select two generations to compare\n\n[Tab] Switch list  [j/k] Navigate  [Enter] Select  [o] Path  [p] Profile")
             .style(theme.text_dim())
             .alignment(Alignment::Center)
             .wrap(Wrap { trim: false });
@@ -594,7 +805,7 @@ fn render_diff_results(
     }
 
     if let Some(diff) = &app.current_diff {
-        render_diff_content(frame, diff, app.diff_scroll, theme, inner);
+        render_diff_content(frame, diff, app.diff_scroll, app.diff_kind_filter, theme, inner);
     } else {
         let loading = Paragraph::new("Calculating diff...")
             .style(theme.text_dim())
@@ -608,6 +819,7 @@ fn render_diff_content(
     frame: &mut Frame,
     diff: &GenerationDiff,
     scroll: usize,
+    kind_filter: Option<UpdateKind>,
     theme: &Theme,
     area: Rect,
 ) {
@@ -624,12 +836,17 @@ fn render_diff_content(
             theme.diff_added(),
         ));
         for pkg in &diff.added {
-            lines.push(Line::from(vec![
+            let mut spans = vec![
                 Span::styled(" + ", theme.diff_added()),
                 Span::styled(&pkg.name, theme.text()),
                 Span::raw(" "),
                 Span::styled(&pkg.version, theme.text_dim()),
-            ]));
+            ];
+            if pkg.size > 0 {
+                let size = format_size_with_change(pkg.size, pkg.size as i64);
+                spans.push(Span::styled(format!("  {}", size), theme.text_dim()));
+            }
+            lines.push(Line::from(spans));
         }
         lines.push(Line::raw(""));
     }
@@ -641,31 +858,48 @@ fn render_diff_content(
             theme.diff_removed(),
         ));
         for pkg in &diff.removed {
-            lines.push(Line::from(vec![
+            let mut spans = vec![
                 Span::styled(" - ", theme.diff_removed()),
                 Span::styled(&pkg.name, theme.text()),
                 Span::raw(" "),
                 Span::styled(&pkg.version, theme.text_dim()),
-            ]));
+            ];
+            if pkg.size > 0 {
+                let size = format_size_with_change(pkg.size, -(pkg.size as i64));
+                spans.push(Span::styled(format!("  {}", size), theme.text_dim()));
+            }
+            lines.push(Line::from(spans));
         }
         lines.push(Line::raw(""));
     }
 
     // Updated
     if !diff.updated.is_empty() {
-        lines.push(Line::styled(
-            format!("Updated ({})", diff.updated.len()),
-            theme.diff_updated(),
-        ));
-        for upd in &diff.updated {
+        let updates: Vec<_> = diff
+            .updated
+            .iter()
+            .filter(|u| kind_filter.is_none_or(|kind| u.kind == kind))
+            .collect();
+        let header = match kind_filter {
+            Some(kind) => format!("Updated ({} of {} · {} only)", updates.len(), diff.updated.len(), kind.label()),
+            None => format!("Updated ({})", diff.updated.len()),
+        };
+        lines.push(Line::styled(header, theme.diff_updated()));
+        for upd in updates {
+            let kind_style = theme.update_kind(upd.kind);
             let mut spans = vec![
-                Span::styled(" ~ ", theme.diff_updated()),
+                Span::styled(" ~ ", kind_style),
                 Span::styled(&upd.name, theme.text()),
                 Span::raw(" "),
                 Span::styled(&upd.old_version, theme.text_dim()),
                 Span::raw(" → "),
-                Span::styled(&upd.new_version, theme.text()),
+                Span::styled(&upd.new_version, kind_style),
+                Span::styled(format!(" {}", upd.kind.label()), kind_style),
             ];
+            if upd.old_size > 0 || upd.new_size > 0 {
+                let size = format_size_with_change(upd.new_size, upd.size_change());
+                spans.push(Span::styled(format!("  {}", size), theme.text_dim()));
+            }
             if upd.is_kernel {
                 spans.push(Span::styled(" ⚠ Kernel", theme.warning()));
             } else if upd.is_security {
@@ -675,6 +909,31 @@ fn render_diff_content(
         }
     }
 
+    // Rebuilt (full-closure diffs only)
+    if !diff.rebuilt.is_empty() {
+        if !diff.updated.is_empty() {
+            lines.push(Line::raw(""));
+        }
+        lines.push(Line::styled(
+            format!("Rebuilt ({})", diff.rebuilt.len()),
+            theme.title(),
+        ));
+        for rebuild in &diff.rebuilt {
+            let cause = if rebuild.caused_by.is_empty() {
+                "build-time change".to_string()
+            } else {
+                rebuild.caused_by.join(", ")
+            };
+            lines.push(Line::from(vec![
+                Span::styled(" ⟳ ", theme.title()),
+                Span::styled(&rebuild.name, theme.text()),
+                Span::raw(" "),
+                Span::styled(&rebuild.version, theme.text_dim()),
+                Span::styled(format!("  ← {}", cause), theme.text_dim()),
+            ]));
+        }
+    }
+
     // Apply scroll
     let visible_lines: Vec<Line> = lines
         .into_iter()
@@ -708,10 +967,7 @@ fn render_manage_tab(frame: &mut Frame, app: &App, area: Rect) {
         width: inner.width,
         height: 1,
     };
-    let profile_label = format!(
-        "Profile: [{}]  (Tab to switch)",
-        if app.manage_profile == ProfileType::System { "System" } else { "Home-Manager" }
-    );
+    let profile_label = format!("Profile: [{}]  (Tab to switch)", app.manage_profile.name());
     let profile_widget = Paragraph::new(profile_label).style(theme.text());
     frame.render_widget(profile_widget, profile_area);
 
@@ -723,11 +979,7 @@ fn render_manage_tab(frame: &mut Frame, app: &App, area: Rect) {
         height: inner.height.saturating_sub(6),
     };
 
-    let generations = if app.manage_profile == ProfileType::System {
-        &app.system_generations
-    } else {
-        app.home_manager_generations.as_ref().unwrap_or(&app.system_generations)
-    };
+    let generations = app.generations_for(&app.manage_profile);
 
     // Header
     let header = Row::new(vec![
@@ -750,13 +1002,15 @@ fn render_manage_tab(frame: &mut Frame, app: &App, area: Rect) {
             };
 
             let status = if gen.is_current {
-                "● current"
+                Cell::from("● current")
             } else if gen.is_pinned {
-                "★ pinned"
+                Cell::from("★ pinned")
             } else if gen.in_bootloader {
-                "⚡ boot"
+                Cell::from("⚡ boot")
+            } else if app.policy_candidates.contains(&gen.id) {
+                Cell::from("✗ policy").style(theme.warning())
             } else {
-                ""
+                Cell::from("")
             };
 
             let style = if i == app.manage_cursor {
@@ -770,7 +1024,7 @@ fn render_manage_tab(frame: &mut Frame, app: &App, area: Rect) {
                 Cell::from(format!("#{}", gen.id)),
                 Cell::from(gen.formatted_date()),
                 Cell::from(gen.formatted_size()),
-                Cell::from(status),
+                status,
             ])
             .style(style)
         })
@@ -805,7 +1059,7 @@ fn render_manage_tab(frame: &mut Frame, app: &App, area: Rect) {
             selected_count
         )
     } else {
-        "[Space] Select  [A] Select All  [R] Restore  [P] Pin/Unpin".to_string()
+        "[Space] Select  [A] Select All  [X] Select Policy  [R] Restore  [P] Pin/Unpin".to_string()
     };
 
     let actions_widget = Paragraph::new(actions_text)
@@ -837,6 +1091,7 @@ fn render_settings_tab(frame: &mut Frame, app: &App, area: Rect) {
         ("Show Package Count", bool_str(app.config.display.show_package_count)),
         ("Show Size", bool_str(app.config.display.show_size)),
         ("Show Boot Entry", bool_str(app.config.display.show_boot_entry)),
+        ("Show Closure Count", bool_str(app.config.display.show_closure_count)),
     ];
 
     let items: Vec<ListItem> = settings
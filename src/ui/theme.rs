@@ -4,6 +4,7 @@
 //! Each theme defines colors for all UI elements.
 
 use crate::config::ThemeName;
+use crate::types::UpdateKind;
 use ratatui::style::{Color, Modifier, Style};
 
 /// Complete theme with all required colors
@@ -16,7 +17,6 @@ pub struct Theme {
     
     // Accent colors
     pub accent: Color,
-    pub accent_dim: Color,
     
     // Status colors
     pub success: Color,
@@ -33,11 +33,12 @@ pub struct Theme {
     pub diff_added: Color,
     pub diff_removed: Color,
     pub diff_updated: Color,
+    pub diff_pre_release: Color,
+    pub diff_unstable: Color,
     
     // Special indicators
     pub current_marker: Color,
     pub pinned_marker: Color,
-    pub boot_marker: Color,
     
     // Internal flag for transparent mode
     is_transparent: bool,
@@ -63,7 +64,6 @@ impl Theme {
             
             // Accent (orange)
             accent: Color::Rgb(254, 128, 25),     // #fe8019
-            accent_dim: Color::Rgb(214, 93, 14),  // #d65d0e
             
             // Status
             success: Color::Rgb(184, 187, 38),    // #b8bb26
@@ -80,11 +80,12 @@ impl Theme {
             diff_added: Color::Rgb(184, 187, 38),    // #b8bb26 (green)
             diff_removed: Color::Rgb(251, 73, 52),   // #fb4934 (red)
             diff_updated: Color::Rgb(131, 165, 152), // #83a598 (blue)
+            diff_pre_release: Color::Rgb(211, 134, 155), // #d3869b (purple)
+            diff_unstable: Color::Rgb(142, 192, 124),    // #8ec07c (aqua)
             
             // Markers
             current_marker: Color::Rgb(184, 187, 38),   // green
             pinned_marker: Color::Rgb(250, 189, 47),    // yellow
-            boot_marker: Color::Rgb(131, 165, 152),     // blue
             
             is_transparent: false,
         }
@@ -100,7 +101,6 @@ impl Theme {
             
             // Accent (Frost - blue)
             accent: Color::Rgb(136, 192, 208),    // #88c0d0
-            accent_dim: Color::Rgb(94, 129, 172), // #5e81ac
             
             // Status (Aurora)
             success: Color::Rgb(163, 190, 140),   // #a3be8c (green)
@@ -117,11 +117,12 @@ impl Theme {
             diff_added: Color::Rgb(163, 190, 140),   // green
             diff_removed: Color::Rgb(191, 97, 106),  // red
             diff_updated: Color::Rgb(129, 161, 193), // blue
+            diff_pre_release: Color::Rgb(180, 142, 173), // purple
+            diff_unstable: Color::Rgb(143, 188, 187),    // frost green
             
             // Markers
             current_marker: Color::Rgb(163, 190, 140),
             pinned_marker: Color::Rgb(235, 203, 139),
-            boot_marker: Color::Rgb(136, 192, 208),
             
             is_transparent: false,
         }
@@ -137,7 +138,6 @@ impl Theme {
             
             // Accent
             accent: Color::Cyan,
-            accent_dim: Color::Blue,
             
             // Status
             success: Color::Green,
@@ -154,11 +154,12 @@ impl Theme {
             diff_added: Color::Green,
             diff_removed: Color::Red,
             diff_updated: Color::Blue,
+            diff_pre_release: Color::Magenta,
+            diff_unstable: Color::LightGreen,
             
             // Markers
             current_marker: Color::Green,
             pinned_marker: Color::Yellow,
-            boot_marker: Color::Cyan,
             
             is_transparent: true,
         }
@@ -307,6 +308,23 @@ impl Theme {
         }
     }
 
+    /// Updated item in diff, colored by the kind of version change
+    pub fn update_kind(&self, kind: UpdateKind) -> Style {
+        let style = match kind {
+            UpdateKind::Major => Style::default().fg(self.accent),
+            UpdateKind::Minor => Style::default().fg(self.warning),
+            UpdateKind::Patch => Style::default().fg(self.diff_updated),
+            UpdateKind::Downgrade => Style::default().fg(self.error).add_modifier(Modifier::BOLD),
+            UpdateKind::PreRelease => Style::default().fg(self.diff_pre_release),
+            UpdateKind::UnstableDate => Style::default().fg(self.diff_unstable),
+        };
+        if self.is_transparent {
+            style
+        } else {
+            style.bg(self.bg)
+        }
+    }
+
     /// Current generation marker
     pub fn marker_current(&self) -> Style {
         Style::default()
@@ -319,11 +337,6 @@ impl Theme {
         Style::default().fg(self.pinned_marker)
     }
 
-    /// Boot entry marker
-    pub fn marker_boot(&self) -> Style {
-        Style::default().fg(self.boot_marker)
-    }
-
     /// Block background style - use this for Block widgets
     pub fn block_style(&self) -> Style {
         if self.is_transparent {
@@ -5,6 +5,7 @@
 //! - Progress indicators
 //! - Custom list rendering
 
+use crate::tasks::Progress;
 use crate::ui::Theme;
 use ratatui::{
     layout::{Alignment, Constraint, Layout, Rect},
@@ -190,10 +191,11 @@ pub fn render_undo_popup(
     );
 }
 
-/// Render a loading indicator
+/// Render a loading indicator with optional progress
 pub fn render_loading(
     frame: &mut Frame,
     message: &str,
+    progress: Option<&Progress>,
     theme: &Theme,
     area: Rect,
 ) {
@@ -203,6 +205,22 @@ pub fn render_loading(
         .unwrap()
         .as_millis() / 100) as usize % spinner_frames.len();
 
+    let progress_line = match progress {
+        Some(progress) if progress.total > 0 => {
+            let bar_width = 16;
+            let filled = bar_width * progress.done.min(progress.total) / progress.total;
+            Line::from(vec![
+                Span::styled("█".repeat(filled), Style::default().fg(theme.accent)),
+                Span::styled("░".repeat(bar_width - filled), theme.text_dim()),
+                Span::styled(
+                    format!(" {}/{} {}", progress.done, progress.total, progress.label),
+                    theme.text_dim(),
+                ),
+            ])
+        }
+        _ => Line::raw(""),
+    };
+
     let content = vec![
         Line::raw(""),
         Line::from(vec![
@@ -210,11 +228,13 @@ pub fn render_loading(
             Span::raw(" "),
             Span::styled(message, theme.text()),
         ]),
+        progress_line,
+        Line::styled("Esc to cancel", theme.text_dim()),
         Line::raw(""),
     ];
 
-    let popup_width = 40.min(area.width.saturating_sub(4));
-    let popup_area = centered_rect(popup_width, 5, area);
+    let popup_width = 44.min(area.width.saturating_sub(4));
+    let popup_area = centered_rect(popup_width, 7, area);
 
     frame.render_widget(Clear, popup_area);
 
@@ -229,7 +249,7 @@ pub fn render_loading(
         x: popup_area.x + 2,
         y: popup_area.y + 1,
         width: popup_area.width.saturating_sub(4),
-        height: 3,
+        height: 5,
     };
 
     let loading = Paragraph::new(content)
@@ -317,11 +337,6 @@ pub fn horizontal_split(area: Rect, percentages: &[u16]) -> Vec<Rect> {
     Layout::horizontal(constraints).split(area).to_vec()
 }
 
-/// Helper: Create vertical layout with given constraints
-pub fn vertical_layout(area: Rect, constraints: Vec<Constraint>) -> Vec<Rect> {
-    Layout::vertical(constraints).split(area).to_vec()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;